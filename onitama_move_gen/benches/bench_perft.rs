@@ -1,5 +1,5 @@
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
-use onitama_move_gen::perft::perft_test;
+use onitama_move_gen::{perft::perft_test, random::Generator};
 
 fn bench_perft(c: &mut Criterion) {
     let mut group = c.benchmark_group("perft");
@@ -11,5 +11,19 @@ fn bench_perft(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_perft);
+// move generation on positions from the middle of random games
+fn bench_random(c: &mut Criterion) {
+    let mut generator = Generator::new(0);
+    let games: Vec<_> = (0..100).map(|_| generator.playout(8)).collect();
+    c.bench_function("forward_random", |b| {
+        b.iter(|| {
+            games
+                .iter()
+                .map(|game| game.forward().count())
+                .sum::<usize>()
+        })
+    });
+}
+
+criterion_group!(benches, bench_perft, bench_random);
 criterion_main!(benches);
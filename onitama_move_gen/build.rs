@@ -2,6 +2,7 @@ extern crate build_const;
 extern crate fastrand;
 use build_const::ConstWriter;
 
+// name, moves from the center square and the stamp color, 0 is blue and 1 is red
 #[allow(clippy::unusual_byte_groupings)]
 const CARDS: [(&str, u32, u8); 16] = [
     ("ox", 0b00000_00100_00010_00100_00000, 0),
@@ -42,6 +43,7 @@ fn main() {
     let mut shifted_l = [[0; 25]; 16];
     let mut shifted_u = [[0; 25]; 16];
     let mut names = [""; 16];
+    let mut colors = [0u8; 16];
     for card in 0..16 {
         let m = CARDS[card].1;
         let r = CARDS[card].1.reverse_bits() >> 7;
@@ -54,6 +56,7 @@ fn main() {
             shifted_u[card][pos] = (m as u64) << 32;
         }
         names[card] = CARDS[card].0;
+        colors[card] = CARDS[card].2;
     }
 
     let consts = ConstWriter::for_build("lut").unwrap();
@@ -63,5 +66,6 @@ fn main() {
     consts.add_value("SHIFTED_L", "[[u64; 25]; 16]", shifted_l);
     consts.add_value("SHIFTED_U", "[[u64; 25]; 16]", shifted_u);
     consts.add_value("NAMES", "[&str; 16]", names);
+    consts.add_value("COLORS", "[u8; 16]", colors);
     consts.finish();
 }
@@ -0,0 +1,179 @@
+#[cfg(feature = "v2")]
+use onitama_move_gen_2::{for_each_iter::ForEachIter, side::Side, state::State};
+
+use crate::gen::Game;
+
+// a move generator that the search and the tablebase can be built on, the
+// positions go in and out as `Game` so the tree and the table keep one layout
+pub trait MoveGen {
+    // calls `f` with each move of `game` and its index in `game.forward()`
+    // until `f` returns `None`
+    //
+    // without `evasions` every move is generated
+    // with `evasions` only the moves after which the other player can not win
+    // in one are generated, the player to move must not be able to win in one
+    fn try_for_each_move(
+        game: Game,
+        evasions: bool,
+        f: impl FnMut(usize, Game) -> Option<()>,
+    ) -> Option<()>;
+
+    // calls `f` with each position that has a move to `game`, they are not
+    // checked to be reachable
+    // with `uncapture` the positions where that move took a pawn are included
+    // as well, if there is room for the pawn
+    fn for_each_unmove(game: Game, uncapture: bool, f: impl FnMut(Game));
+
+    #[inline]
+    fn for_each_move(game: Game, evasions: bool, mut f: impl FnMut(usize, Game)) {
+        Self::try_for_each_move(game, evasions, |index, new_game| {
+            f(index, new_game);
+            Some(())
+        });
+    }
+}
+
+impl MoveGen for Game {
+    #[inline]
+    fn try_for_each_move(
+        game: Game,
+        evasions: bool,
+        mut f: impl FnMut(usize, Game) -> Option<()>,
+    ) -> Option<()> {
+        let mut moves = game.forward().enumerate();
+        if evasions {
+            let evasions = game.evasions();
+            moves
+                .filter(|(_, new_game)| evasions.contains(new_game))
+                .try_for_each(|(index, new_game)| f(index, new_game))
+        } else {
+            moves.try_for_each(|(index, new_game)| f(index, new_game))
+        }
+    }
+
+    #[inline]
+    fn for_each_unmove(game: Game, uncapture: bool, mut f: impl FnMut(Game)) {
+        let uncapture = uncapture && game.count_pieces() < 5;
+        for (prev_game, take) in game.backward() {
+            f(prev_game);
+            if uncapture {
+                f(Game {
+                    other: prev_game.other | take,
+                    ..prev_game
+                });
+            }
+        }
+    }
+}
+
+// `S` is the side of the player to move, which does not change the moves
+//
+// this backend is there to check v2 against v1 and not to be fast, every call
+// converts between `Game` and `State` and looks up the index of every move
+#[cfg(feature = "v2")]
+impl<S: Side> MoveGen for State<S> {
+    #[inline]
+    fn try_for_each_move(
+        game: Game,
+        evasions: bool,
+        mut f: impl FnMut(usize, Game) -> Option<()>,
+    ) -> Option<()> {
+        // `State` only generates the evasions
+        if !evasions {
+            return Game::try_for_each_move(game, evasions, f);
+        }
+        let mut state = State::<S>::from(game);
+        state.try_for_each(|new_state| {
+            let new_game = Game::from(&*new_state);
+            f(game.move_index(new_game), new_game)
+        })
+    }
+
+    #[inline]
+    fn for_each_unmove(game: Game, uncapture: bool, mut f: impl FnMut(Game)) {
+        // `State` can not hold a game that is over
+        if game.is_loss() || game.is_other_loss() {
+            return Game::for_each_unmove(game, uncapture, f);
+        }
+        let mut state = State::<S>::from(game);
+        state
+            .unmoves(uncapture)
+            .for_each(|prev_state| f(Game::from(&*prev_state)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    #[cfg(feature = "v2")]
+    use onitama_move_gen_2::side::{Left, Right};
+
+    use super::*;
+    use crate::random::Generator;
+
+    fn moves<M: MoveGen>(game: Game, evasions: bool) -> HashSet<(usize, Game)> {
+        let mut moves = HashSet::new();
+        M::for_each_move(game, evasions, |index, new_game| {
+            assert_eq!(game.forward().nth(index), Some(new_game));
+            assert!(moves.insert((index, new_game)));
+        });
+        moves
+    }
+
+    fn unmoves<M: MoveGen>(game: Game, uncapture: bool) -> HashSet<Game> {
+        let mut unmoves = HashSet::new();
+        M::for_each_unmove(game, uncapture, |prev_game| {
+            assert!(unmoves.insert(prev_game));
+        });
+        unmoves
+    }
+
+    #[test]
+    fn test_move_gen() {
+        let mut generator = Generator::new(3);
+        for i in 0..500 {
+            let game = generator.playout(i % 40);
+            if game.is_win() {
+                continue;
+            }
+            let all = moves::<Game>(game, false);
+            assert_eq!(all.len(), game.count_moves());
+            let evasions = moves::<Game>(game, true);
+            assert!(evasions.is_subset(&all));
+            // only moves that lose in one are left out
+            assert!(all
+                .difference(&evasions)
+                .all(|&(_, new_game)| new_game.is_win()));
+            #[cfg(feature = "v2")]
+            for &evasions in &[false, true] {
+                let expected = moves::<Game>(game, evasions);
+                assert_eq!(moves::<State<Left>>(game, evasions), expected);
+                assert_eq!(moves::<State<Right>>(game, evasions), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unmoves() {
+        let mut generator = Generator::new(5);
+        for i in 0..500 {
+            let game = generator.playout(i % 40);
+            let prev_games = unmoves::<Game>(game, false);
+            let with_takes = unmoves::<Game>(game, true);
+            assert!(prev_games.is_subset(&with_takes));
+            // every previous position that is not over goes to `game`
+            for prev_game in &with_takes {
+                if !prev_game.is_loss() {
+                    assert!(prev_game.forward().any(|new_game| new_game == game));
+                }
+            }
+            #[cfg(feature = "v2")]
+            for &uncapture in &[false, true] {
+                let expected = unmoves::<Game>(game, uncapture);
+                assert_eq!(unmoves::<State<Left>>(game, uncapture), expected);
+                assert_eq!(unmoves::<State<Right>>(game, uncapture), expected);
+            }
+        }
+    }
+}
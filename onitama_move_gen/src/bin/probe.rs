@@ -0,0 +1,35 @@
+use std::{env, time::Instant};
+
+use onitama_move_gen::{gen::Game, notation::move_name, tablebase::TableBase};
+
+// usage: probe xxXxx/...../...../...../ooOoo ox boar horse elephant crab
+fn main() {
+    let text = env::args().skip(1).collect::<Vec<_>>().join(" ");
+    let game: Game = match text.parse() {
+        Ok(game) => game,
+        Err(err) => {
+            eprintln!("invalid position: {}", err);
+            return;
+        }
+    };
+
+    let now = Instant::now();
+    let table = TableBase::new(game.all_cards());
+    println!("tablebase took: {}", now.elapsed().as_secs_f32());
+    println!("{}{:?}", game, game);
+
+    match table.probe(game) {
+        Some(eval) => println!("result: {}", eval),
+        None => {
+            println!("result: not in tablebase");
+            return;
+        }
+    }
+
+    if let Some(mut moves) = table.probe_moves(game) {
+        moves.sort_by_key(|&(_, eval)| std::cmp::Reverse(eval));
+        for (new_game, eval) in moves {
+            println!("{}: {}", move_name(game, new_game), eval);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::{env, time::Instant};
+
+use onitama_move_gen::{
+    card::Card,
+    gen::Game,
+    notation::card_id,
+    solve::{Outcome, Solver},
+};
+
+// usage: solve <dir> <max pawns> <5 cards, blue's two, red's two and the side card like `Game::initial`>
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 8 {
+        eprintln!("usage: solve <dir> <max pawns> <card> <card> <card> <card> <card>");
+        return;
+    }
+    let max_pawns: u32 = args[2].parse().expect("invalid number of pawns");
+    let mut cards = [0; 5];
+    for (card, name) in cards.iter_mut().zip(&args[3..]) {
+        *card = card_id(name).unwrap_or_else(|| panic!("unknown card: {}", name));
+    }
+
+    let now = Instant::now();
+    let mut solver = Solver::open(&args[1], cards, max_pawns).unwrap();
+    solver
+        .solve(|iteration, (wins, losses)| {
+            println!(
+                "iteration {}: {} wins, {} losses, {}s",
+                iteration,
+                wins,
+                losses,
+                now.elapsed().as_secs()
+            )
+        })
+        .unwrap();
+
+    if max_pawns < 4 {
+        println!("the starting position needs 4 pawns per side");
+        return;
+    }
+    // the color of the side card moves first
+    let first = Card::new(cards[4]).unwrap().color();
+    match solver.outcome(Game::initial(cards)).unwrap() {
+        Outcome::Win => println!("start: win for {}", first),
+        Outcome::Loss => println!("start: win for {}", first.other()),
+        Outcome::Draw => println!("start: draw"),
+    }
+}
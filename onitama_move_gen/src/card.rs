@@ -0,0 +1,176 @@
+use std::{error::Error, fmt::Display, str::FromStr};
+
+use crate::{COLORS, NAMES, SHIFTED};
+
+// the color of the stamp on a card, the player with the side card's color
+// starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    Blue,
+    Red,
+}
+
+impl Color {
+    pub fn other(self) -> Self {
+        match self {
+            Color::Blue => Color::Red,
+            Color::Red => Color::Blue,
+        }
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Color::Blue => "blue",
+            Color::Red => "red",
+        })
+    }
+}
+
+// one of the 16 cards, by the same id as `Game::cards` and `Game::table`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Card(u32);
+
+impl Card {
+    pub fn all() -> impl Iterator<Item = Card> {
+        (0..16).map(Card)
+    }
+
+    pub fn new(id: u32) -> Option<Self> {
+        if id < 16 {
+            Some(Card(id))
+        } else {
+            None
+        }
+    }
+
+    pub fn id(self) -> u32 {
+        self.0
+    }
+
+    pub fn name(self) -> &'static str {
+        NAMES[self.0 as usize]
+    }
+
+    pub fn color(self) -> Color {
+        match COLORS[self.0 as usize] {
+            0 => Color::Blue,
+            _ => Color::Red,
+        }
+    }
+
+    // the squares that can be reached from the center square, in the layout of
+    // the player holding the card
+    pub fn moves(self) -> u32 {
+        SHIFTED[self.0 as usize][12]
+    }
+
+    // 5 lines of 5 squares, the center is '*' and the moves are '#', seen by the
+    // player holding the card or, when `flip` is set, by the other player
+    pub fn pattern(self, flip: bool) -> String {
+        let moves = self.moves();
+        let mut text = String::new();
+        for row in 0..5 {
+            for col in 0..5 {
+                // the holder moves forward to higher rows and sees them at the top
+                let pos = if flip {
+                    row * 5 + col
+                } else {
+                    24 - row * 5 - col
+                };
+                text.push(match pos {
+                    12 => '*',
+                    _ if moves & 1 << pos != 0 => '#',
+                    _ => '.',
+                });
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+impl Display for Card {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCardError(String);
+
+impl Display for ParseCardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown card: {}", self.0)
+    }
+}
+
+impl Error for ParseCardError {}
+
+impl FromStr for Card {
+    type Err = ParseCardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        Card::all()
+            .find(|card| card.name() == name)
+            .ok_or_else(|| ParseCardError(s.to_string()))
+    }
+}
+
+// the patterns of `cards` next to each other with their names above them,
+// every card with its own `flip`
+pub fn patterns(cards: &[(Card, bool)]) -> String {
+    let mut lines = vec![String::new(); 6];
+    for &(card, flip) in cards {
+        lines[0] += &format!("{:<10}", card.name());
+        for (line, row) in lines[1..].iter_mut().zip(card.pattern(flip).lines()) {
+            *line += &format!("{:<10}", row);
+        }
+    }
+    lines
+        .iter()
+        .map(|line| line.trim_end().to_string() + "\n")
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen::Game;
+
+    #[test]
+    fn test_card() {
+        let tiger: Card = "Tiger".parse().unwrap();
+        assert_eq!(tiger, Card::new(5).unwrap());
+        assert_eq!(tiger.to_string(), "tiger");
+        assert_eq!(tiger.color(), Color::Blue);
+        assert_eq!("dragon".parse::<Card>().unwrap().color(), Color::Red);
+        assert!("dog".parse::<Card>().is_err());
+        assert_eq!(Card::new(16), None);
+
+        // two forward and one back
+        assert_eq!(tiger.pattern(false), "..#..\n.....\n..*..\n..#..\n.....\n");
+        assert_eq!(tiger.pattern(true), ".....\n..#..\n..*..\n.....\n..#..\n");
+        // rabbit moves to the right for its holder
+        let rabbit: Card = "rabbit".parse().unwrap();
+        assert_eq!(rabbit.pattern(false), ".....\n...#.\n..*.#\n.#...\n.....\n");
+        assert_eq!(
+            patterns(&[(tiger, false), (rabbit, true)]),
+            "tiger     rabbit\n..#..     .....\n.....     ...#.\n..*..     #.*..\n..#..     .#...\n.....     .....\n"
+        );
+    }
+
+    // the player with the color of the side card moves first
+    #[test]
+    fn test_initial() {
+        // crab is blue and frog is red
+        assert_eq!(Card::new(4).unwrap().color(), Color::Blue);
+        assert_eq!(Card::new(10).unwrap().color(), Color::Red);
+        let game = Game::initial([0, 1, 2, 3, 4]);
+        assert_eq!((game.cards, game.table), (0b00011 | 0b01100 << 16, 4));
+        let game = Game::initial([0, 1, 2, 3, 10]);
+        assert_eq!((game.cards, game.table), (0b01100 | 0b00011 << 16, 10));
+    }
+}
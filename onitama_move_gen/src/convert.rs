@@ -0,0 +1,217 @@
+use std::marker::PhantomData;
+
+use onitama_move_gen_2::{side::Side, state::State};
+
+use crate::{
+    gen::{Game, My, Other, PIECE_MASK},
+    ops::{BitIter, CardIter},
+};
+
+// `State` is 6 squares wide with a gutter and the players start on the left and
+// the right instead of at the bottom and the top, the card ids are different too
+
+// the id in `State` of every card of `Game`
+const STATE_CARDS: [u32; 16] = [14, 7, 10, 5, 1, 0, 2, 3, 4, 6, 8, 12, 9, 13, 11, 15];
+
+fn state_card(card: u32) -> u32 {
+    STATE_CARDS[card as usize]
+}
+
+fn game_card(card: u32) -> u32 {
+    STATE_CARDS.iter().position(|&c| c == card).unwrap() as u32
+}
+
+// a square from the point of view of player `S` to an index in `State`, the
+// rows of `Game` are the columns of `State`
+fn state_index<S: Side>(pos: u32) -> u32 {
+    let (row, col) = (pos / 5, pos % 5);
+    S::get((col * 6 + 4 - row, (4 - col) * 6 + row))
+}
+
+fn game_pos<S: Side>(index: u32) -> u32 {
+    let (y, x) = (index / 6, index % 6);
+    S::get(((4 - x) * 5 + y, x * 5 + 4 - y))
+}
+
+fn state_pawns<S: Side>(pieces: u32, king: u32) -> u32 {
+    let pawns = pieces & PIECE_MASK & !(1 << king);
+    BitIter(pawns).fold(0, |pawns, pos| pawns | 1 << state_index::<S>(pos))
+}
+
+fn state_cards(cards: u32) -> u16 {
+    CardIter::new(cards & 0xffff).fold(0, |cards, card| cards | 1 << state_card(card))
+}
+
+// `S` is the side of the player to move, the king can not be taken yet
+impl<S: Side> From<Game> for State<S> {
+    fn from(game: Game) -> Self {
+        let (my_king, other_king) = (game.king::<My>(), game.king::<Other>());
+        let my = (
+            state_pawns::<S>(game.my, my_king),
+            state_index::<S>(my_king),
+            state_cards(game.cards),
+        );
+        let other = (
+            state_pawns::<S::Other>(game.other, other_king),
+            state_index::<S::Other>(other_king),
+            state_cards(game.cards >> 16),
+        );
+        // the pairs are ordered left, right
+        let (left, right) = S::get(((my, other), (other, my)));
+        State {
+            pawns: (left.0, right.0),
+            kings: (left.1, right.1),
+            cards: (left.2, right.2),
+            table: state_card(game.table),
+            side: PhantomData,
+        }
+    }
+}
+
+impl<S: Side> From<&State<S>> for Game {
+    fn from(state: &State<S>) -> Self {
+        fn pieces<S: Side>(pawns: u32, king: u32) -> u32 {
+            let king = game_pos::<S>(king);
+            BitIter(pawns).fold(king << 25 | 1 << king, |pieces, index| {
+                pieces | 1 << game_pos::<S>(index)
+            })
+        }
+        fn cards(cards: u16) -> u32 {
+            (0..16)
+                .filter(|card| cards & 1 << card != 0)
+                .fold(0, |all, card| all | 1 << game_card(card))
+        }
+
+        Game {
+            my: pieces::<S>(state.my_pawns(), state.my_king()),
+            other: pieces::<S::Other>(state.opp_pawns(), state.opp_king()),
+            cards: cards(S::get(state.cards)) | cards(S::Other::get(state.cards)) << 16,
+            table: game_card(state.table),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use onitama_move_gen_2::{
+        card::all_mask,
+        for_each_iter::ForEachIter,
+        perft,
+        side::{Left, Right},
+    };
+
+    use super::*;
+    use crate::{perft::perft_evasions, random::Generator, SHIFTED};
+
+    // every card moves the same from every square on both sides
+    fn check_card<S: Side>(card: u32) {
+        for pos in 0..25 {
+            let moves = BitIter(SHIFTED[card as usize][pos as usize])
+                .fold(0, |moves, to| moves | 1 << state_index::<S>(to));
+            let from = 1 << state_index::<S>(pos);
+            assert_eq!(all_mask::<S>(from, state_card(card)), moves);
+        }
+    }
+
+    #[test]
+    fn test_cards() {
+        for card in 0..16 {
+            assert_eq!(game_card(state_card(card)), card);
+            check_card::<Left>(card);
+            check_card::<Right>(card);
+        }
+    }
+
+    // the successors that do not lose in one
+    fn game_successors(game: Game) -> HashSet<Game> {
+        let evasions = game.evasions();
+        game.forward()
+            .filter(|new_game| evasions.contains(new_game))
+            .collect()
+    }
+
+    fn state_successors<S: Side>(game: Game) -> HashSet<Game> {
+        let mut state = State::<S>::from(game);
+        assert_eq!(Game::from(&state), game);
+        let mut successors = HashSet::new();
+        state.for_each(|new_state| {
+            successors.insert(Game::from(&*new_state));
+        });
+        successors
+    }
+
+    #[test]
+    fn test_cross_validation() {
+        let mut generator = Generator::new(2);
+        let mut games: Vec<Game> = (0..2000).map(|i| generator.playout(i % 30)).collect();
+        games.extend((0..1000).map(|i| generator.endgame(i % 5 + 1)));
+
+        let mut checked = 0;
+        let mut diverging = vec![];
+        // `State` assumes that there is no win in one
+        for &game in games.iter().filter(|game| !game.is_win()) {
+            let expected = game_successors(game);
+            let left = state_successors::<Left>(game);
+            let right = state_successors::<Right>(game);
+            if left != expected || right != expected {
+                diverging.push(game);
+            }
+            checked += 1;
+        }
+        for game in &diverging {
+            println!("{}", game);
+        }
+        assert!(checked > 1000);
+        assert_eq!(diverging.len(), 0, "diverging positions: {:?}", diverging);
+    }
+
+    // the start position of `State` has its own deal, so its counts are not the
+    // ones of `perft_evasions_test`
+    #[test]
+    fn test_perft() {
+        let game = Game::from(&State::<Left>::default());
+        for depth in 1..=5 {
+            assert_eq!(perft_evasions(game, depth), perft::perft_test(depth));
+        }
+    }
+
+    // the previous positions with and without a pawn taken in the last move
+    fn game_predecessors(game: Game) -> HashSet<Game> {
+        let mut predecessors = HashSet::new();
+        for (prev_game, take) in game.backward() {
+            predecessors.insert(prev_game);
+            if game.count_pieces() < 5 {
+                predecessors.insert(Game {
+                    other: prev_game.other | take,
+                    ..prev_game
+                });
+            }
+        }
+        predecessors
+    }
+
+    fn state_predecessors<S: Side>(game: Game) -> HashSet<Game> {
+        let mut state = State::<S>::from(game);
+        let mut predecessors = HashSet::new();
+        state.unmoves(true).for_each(|prev_state| {
+            assert!(predecessors.insert(Game::from(&*prev_state)));
+        });
+        predecessors
+    }
+
+    #[test]
+    fn test_unmoves() {
+        let mut generator = Generator::new(4);
+        for i in 0..1000 {
+            let game = generator.playout(i % 30);
+            if game.is_loss() {
+                continue;
+            }
+            let expected = game_predecessors(game);
+            assert_eq!(state_predecessors::<Left>(game), expected, "{:?}", game);
+            assert_eq!(state_predecessors::<Right>(game), expected, "{:?}", game);
+        }
+    }
+}
@@ -4,6 +4,7 @@ use std::{
 };
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[repr(transparent)]
 pub struct Eval(pub i8);
 
 impl Display for Eval {
@@ -4,6 +4,7 @@ use std::iter::once;
 use bitintr::{Andn, Popcnt};
 use nudge::assume;
 
+use crate::card::{Card, Color};
 use crate::ops::{cards_or, BitIter, CardIter};
 use crate::{SHIFTED, SHIFTED_L, SHIFTED_R, SHIFTED_U};
 
@@ -72,7 +73,7 @@ impl Game {
             if both & other_king != 0 {
                 return true;
             }
-            if from == self.king::<My>() && both & (1 << TEMPLE) != 0 {
+            if from == self.king::<My>() && both & !self.my & (1 << TEMPLE) != 0 {
                 return true;
             }
         }
@@ -94,6 +95,87 @@ impl Game {
         self.my.wrapping_shr(25) == 22 || self.other & 1 << self.other.wrapping_shr(25) == 0
     }
 
+    // the other player could win in one if it was their turn
+    #[inline]
+    pub fn is_threatened(&self) -> bool {
+        self.swap().is_win()
+    }
+
+    // this assumes that there is no win in one for the player to move
+    #[inline]
+    pub fn evasions(&self) -> Evasions {
+        let other = self.swap();
+        let my_king = 1 << 24 >> self.king::<My>();
+        let mut attacks = 0u32;
+        let mut threats = 0u32;
+        let mut temple = false;
+        for from in other.piece_iter::<My>() {
+            let shifted = cards_or(&SHIFTED, other.card_iter::<My>(), from);
+            attacks |= shifted;
+            if shifted & my_king != 0 {
+                threats |= 1 << from;
+            }
+            if from == other.king::<My>() && shifted & !other.my & (1 << TEMPLE) != 0 {
+                temple = true;
+            }
+        }
+        let threats = threats.reverse_bits() >> 7;
+        Evasions {
+            my: self.my,
+            my_king: self.king::<My>(),
+            attacks: if temple {
+                PIECE_MASK
+            } else {
+                attacks.reverse_bits() >> 7
+            },
+            pawns: match (temple, threats.popcnt()) {
+                (false, 0) => PIECE_MASK,
+                (false, 1) => threats,
+                _ => 0,
+            },
+        }
+    }
+
+    // the start position of a deal of blue's two cards, red's two cards and the
+    // side card, the color of the side card's stamp moves first
+    pub fn initial(deal: [u32; 5]) -> Game {
+        let blue = 1 << deal[0] | 1 << deal[1];
+        let red = 1 << deal[2] | 1 << deal[3];
+        let (first, second) = match Card::new(deal[4]).unwrap().color() {
+            Color::Blue => (blue, red),
+            Color::Red => (red, blue),
+        };
+        Game {
+            my: 0b11111 | 2 << 25,
+            other: 0b11111 | 2 << 25,
+            cards: first | second << 16,
+            table: deal[4],
+        }
+    }
+
+    // the same position with the other player to move
+    #[inline]
+    pub fn swap(&self) -> Game {
+        Game {
+            my: self.other,
+            other: self.my,
+            cards: self.cards.rotate_left(16),
+            table: self.table,
+        }
+    }
+
+    pub fn all_cards(&self) -> [u32; 5] {
+        let mut my = self.card_iter::<My>();
+        let mut other = self.card_iter::<Other>();
+        [
+            my.next().unwrap(),
+            my.next().unwrap(),
+            other.next().unwrap(),
+            other.next().unwrap(),
+            self.table,
+        ]
+    }
+
     #[inline]
     pub fn king<P: Player>(&self) -> u32 {
         P::my_or_other(self.my, self.other).wrapping_shr(25)
@@ -141,6 +223,25 @@ impl Game {
         }
     }
 
+    // the index of `new_game` in `self.forward()`, which has the moves ordered
+    // by the piece, then the card and then the square it goes to
+    #[inline]
+    pub fn move_index(&self, new_game: Game) -> usize {
+        let from = (self.my & !new_game.other & PIECE_MASK).trailing_zeros();
+        let to = (new_game.other & !self.my & PIECE_MASK).trailing_zeros();
+        let mut index = 0;
+        for piece in self.piece_iter::<My>() {
+            for card in self.card_iter::<My>() {
+                let moves = self.next_to(piece, card).0;
+                if piece == from && card == new_game.table {
+                    return index + (moves & ((1 << to) - 1)).popcnt() as usize;
+                }
+                index += moves.popcnt() as usize;
+            }
+        }
+        unreachable!()
+    }
+
     #[inline]
     pub fn backward(&self) -> GameBackIter {
         let mut to = self.piece_iter::<Other>();
@@ -159,6 +260,29 @@ impl Game {
     }
 }
 
+// the moves after which the other player can not win in one
+#[derive(Clone, Copy, Debug)]
+pub struct Evasions {
+    my: u32,
+    my_king: u32,
+    // squares the king can not go to
+    attacks: u32,
+    // squares pawns can go to
+    pawns: u32,
+}
+
+impl Evasions {
+    #[inline]
+    pub fn contains(&self, new_game: &Game) -> bool {
+        let to = new_game.other & !self.my & PIECE_MASK;
+        if new_game.king::<Other>() != self.my_king {
+            to & self.attacks == 0
+        } else {
+            to & self.pawns != 0
+        }
+    }
+}
+
 pub struct GameIter {
     game: Game,
     from: BitIter,
@@ -281,3 +405,31 @@ impl Player for Other {
         other
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temple_blocked() {
+        // a pawn on the temple keeps the king from moving there
+        for king in (0..24).filter(|&king| king != TEMPLE) {
+            for card in 0..16 {
+                let game = Game {
+                    my: 1 << king | 1 << TEMPLE | king << 25,
+                    other: 1,
+                    cards: 1 << card
+                        | 1 << ((card + 1) % 16)
+                        | (1 << ((card + 2) % 16) | 1 << ((card + 3) % 16)) << 16,
+                    table: (card + 4) % 16,
+                };
+                assert_eq!(
+                    game.is_win(),
+                    game.forward().any(|new_game| new_game.is_loss()),
+                    "{:?}",
+                    game
+                );
+            }
+        }
+    }
+}
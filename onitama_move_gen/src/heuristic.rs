@@ -0,0 +1,203 @@
+use std::{cmp::max, error::Error, fmt::Display, str::FromStr};
+
+use bitintr::Popcnt;
+
+use crate::{
+    gen::{Game, My, Other, PIECE_MASK, TEMPLE},
+    ops::cards_or,
+    SHIFTED,
+};
+
+pub const FEATURES: usize = 5;
+pub const WEIGHT_NAMES: [&str; FEATURES] = ["material", "mobility", "temple", "safety", "tempo"];
+
+// all features are for the player to move minus the same for the other player
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weights {
+    pub material: i32,
+    pub mobility: i32,
+    pub temple: i32,
+    pub safety: i32,
+    pub tempo: i32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            material: 24,
+            mobility: 1,
+            temple: 3,
+            safety: 4,
+            tempo: 1,
+        }
+    }
+}
+
+impl Weights {
+    pub fn to_array(self) -> [i32; FEATURES] {
+        [
+            self.material,
+            self.mobility,
+            self.temple,
+            self.safety,
+            self.tempo,
+        ]
+    }
+
+    pub fn from_array(values: [i32; FEATURES]) -> Self {
+        Self {
+            material: values[0],
+            mobility: values[1],
+            temple: values[2],
+            safety: values[3],
+            tempo: values[4],
+        }
+    }
+
+    #[inline]
+    pub fn evaluate(&self, game: Game) -> i32 {
+        self.to_array()
+            .iter()
+            .zip(features(game).iter())
+            .map(|(w, f)| w * f)
+            .sum()
+    }
+}
+
+// parameter files have one `name value` pair per line, lines starting with '#'
+// are comments and missing names keep their default weight
+impl Display for Weights {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in WEIGHT_NAMES.iter().zip(self.to_array().iter()) {
+            writeln!(f, "{} {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWeightsError(String);
+
+impl Display for ParseWeightsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for ParseWeightsError {}
+
+impl FromStr for Weights {
+    type Err = ParseWeightsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut values = Weights::default().to_array();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let (name, value) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(name), Some(value), None) => (name, value),
+                _ => return Err(ParseWeightsError(format!("invalid line: {}", line))),
+            };
+            let i = WEIGHT_NAMES
+                .iter()
+                .position(|&n| n == name)
+                .ok_or_else(|| ParseWeightsError(format!("unknown weight: {}", name)))?;
+            values[i] = value
+                .parse()
+                .map_err(|_| ParseWeightsError(format!("invalid value: {}", value)))?;
+        }
+        Ok(Weights::from_array(values))
+    }
+}
+
+pub fn features(game: Game) -> [i32; FEATURES] {
+    let other = game.swap();
+    [
+        game.count_pieces() as i32 - other.count_pieces() as i32,
+        game.count_moves() as i32 - other.count_moves() as i32,
+        temple_distance(other.king::<My>()) - temple_distance(game.king::<My>()),
+        threatened(other) - threatened(game),
+        card_reach(game.cards) - card_reach(other.cards),
+    ]
+}
+
+// number of king steps to the temple of the other player
+#[inline]
+pub fn temple_distance(king: u32) -> i32 {
+    let row = (king / 5) as i32 - (TEMPLE / 5) as i32;
+    let col = (king % 5) as i32 - (TEMPLE % 5) as i32;
+    max(row.abs(), col.abs())
+}
+
+// pieces of the player to move that the other player can take next turn
+#[inline]
+fn threatened(game: Game) -> i32 {
+    let mut attacks = 0;
+    for from in game.piece_iter::<Other>() {
+        attacks |= cards_or(&SHIFTED, game.card_iter::<Other>(), from);
+    }
+    let my = (game.my & PIECE_MASK).reverse_bits() >> 7;
+    (attacks & my).popcnt() as i32
+}
+
+// number of squares the two cards of the player to move can reach from the center
+#[inline]
+fn card_reach(cards: u32) -> i32 {
+    let my = cards & 0xffff;
+    (0..16)
+        .filter(|card| my & 1 << card != 0)
+        .map(|card| SHIFTED[card][12].popcnt() as i32)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_antisymmetric() {
+        let start = Game::initial([0, 1, 2, 3, 4]);
+        let mut games = vec![start];
+        games.extend(start.forward());
+        for game in games {
+            let mine = features(game);
+            let theirs = features(game.swap());
+            for i in 0..FEATURES {
+                assert_eq!(mine[i], -theirs[i]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_features() {
+        // the other player is missing two pawns and their king is next to our temple
+        let game: Game = "xxXxx/...../...../..O../o...o ox boar horse elephant crab"
+            .parse()
+            .unwrap();
+        let f = features(game);
+        assert_eq!(f[0], 2);
+        assert_eq!(f[2], 3 - 4);
+        assert_eq!(f[4], (3 + 3) - (3 + 4));
+        assert!(Weights::default().evaluate(game) > 0);
+        assert_eq!(
+            Weights::from_array(Weights::default().to_array()),
+            Weights::default()
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let weights = Weights::from_array([30, 2, -1, 5, 0]);
+        assert_eq!(weights.to_string().parse(), Ok(weights));
+
+        let weights: Weights = "# tuned\nmobility 3\n\n".parse().unwrap();
+        assert_eq!(weights.mobility, 3);
+        assert_eq!(weights.material, Weights::default().material);
+
+        assert!("speed 1".parse::<Weights>().is_err());
+        assert!("material".parse::<Weights>().is_err());
+        assert!("material x".parse::<Weights>().is_err());
+    }
+}
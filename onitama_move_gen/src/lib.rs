@@ -6,13 +6,26 @@
 #[macro_use]
 extern crate build_const;
 extern crate bitintr;
+extern crate fastrand;
 extern crate nudge;
 extern crate num_traits;
+#[cfg(feature = "v2")]
+extern crate onitama_move_gen_2;
 
+pub mod backend;
+pub mod card;
+#[cfg(feature = "v2")]
+pub mod convert;
 pub mod eval;
 pub mod gen;
+pub mod heuristic;
+pub mod notation;
 pub mod ops;
 pub mod perft;
+pub mod random;
+pub mod score;
+pub mod solve;
 pub mod tablebase;
+pub mod tune;
 
 build_const!("lut");
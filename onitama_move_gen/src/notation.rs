@@ -0,0 +1,188 @@
+use std::{error::Error, fmt::Display, str::FromStr};
+
+use crate::{
+    gen::{Game, My, Other},
+    ops::CardIter,
+    NAMES,
+};
+
+// positions are written like `Game`'s debug layout, rows separated by '/',
+// followed by my two cards, the other two cards and the table card, e.g.
+// "xxXxx/...../...../...../ooOoo ox boar horse elephant crab"
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseGameError(String);
+
+impl Display for ParseGameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for ParseGameError {}
+
+pub fn card_id(name: &str) -> Option<u32> {
+    NAMES.iter().position(|&n| n == name).map(|i| i as u32)
+}
+
+// same coordinates as the litama board, seen from the player to move
+pub fn square_name(pos: u32) -> String {
+    let row = pos / 5;
+    let col = pos % 5;
+    [
+        "edcba".chars().nth(col as usize).unwrap(),
+        "12345".chars().nth(row as usize).unwrap(),
+    ]
+    .iter()
+    .collect()
+}
+
+pub fn move_name(game: Game, new_game: Game) -> String {
+    let from = game.my & !new_game.other;
+    let to = new_game.other & !game.my;
+    format!(
+        "{} {}{}",
+        NAMES[new_game.table as usize],
+        square_name(from.trailing_zeros()),
+        square_name(to.trailing_zeros())
+    )
+}
+
+impl Display for Game {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for i in 0..5 {
+            if i != 0 {
+                f.write_str("/")?;
+            }
+            for j in 0..5 {
+                let pos = i * 5 + j;
+                if self.my & 1 << pos != 0 && self.king::<My>() == pos {
+                    f.write_str("X")?;
+                } else if self.my & 1 << pos != 0 {
+                    f.write_str("x")?;
+                } else if self.other & 1 << 24 >> pos != 0 && self.king::<Other>() == 24 - pos {
+                    f.write_str("O")?;
+                } else if self.other & 1 << 24 >> pos != 0 {
+                    f.write_str("o")?;
+                } else {
+                    f.write_str(".")?;
+                }
+            }
+        }
+        for card in CardIter::new(self.cards & 0xffff)
+            .chain(CardIter::new(self.cards >> 16))
+            .chain(Some(self.table))
+        {
+            write!(f, " {}", NAMES[card as usize])?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Game {
+    type Err = ParseGameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let board = parts
+            .next()
+            .ok_or_else(|| ParseGameError("missing board".to_string()))?;
+
+        let mut game = Game::default();
+        let mut kings = (None, None);
+        let rows: Vec<&str> = board.split('/').collect();
+        if rows.len() != 5 || rows.iter().any(|row| row.chars().count() != 5) {
+            return Err(ParseGameError(format!("expected 5 rows of 5: {}", board)));
+        }
+        for (i, c) in rows.concat().chars().enumerate() {
+            let pos = i as u32;
+            match c {
+                '.' => {}
+                'x' => game.my |= 1 << pos,
+                'o' => game.other |= 1 << (24 - pos),
+                'X' if kings.0.is_none() => {
+                    game.my |= 1 << pos;
+                    kings.0 = Some(pos);
+                }
+                'O' if kings.1.is_none() => {
+                    game.other |= 1 << (24 - pos);
+                    kings.1 = Some(24 - pos);
+                }
+                c => return Err(ParseGameError(format!("unexpected square: {}", c))),
+            }
+        }
+        match kings {
+            (Some(my), Some(other)) => {
+                game.my |= my << 25;
+                game.other |= other << 25;
+            }
+            _ => return Err(ParseGameError("expected one king each".to_string())),
+        }
+
+        let mut cards = [0; 5];
+        for card in cards.iter_mut() {
+            let name = parts
+                .next()
+                .ok_or_else(|| ParseGameError("expected 5 cards".to_string()))?;
+            *card =
+                card_id(name).ok_or_else(|| ParseGameError(format!("unknown card: {}", name)))?;
+        }
+        if parts.next().is_some() {
+            return Err(ParseGameError("expected 5 cards".to_string()));
+        }
+        let all = cards.iter().fold(0u32, |all, card| all | 1 << card);
+        if all.count_ones() != 5 {
+            return Err(ParseGameError("cards must be different".to_string()));
+        }
+        game.cards = 1 << cards[0] | 1 << cards[1] | (1 << cards[2] | 1 << cards[3]) << 16;
+        game.table = cards[4];
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let text = "xxXxx/...../...../...../ooOoo ox boar horse elephant crab";
+        let game: Game = text.parse().unwrap();
+        assert_eq!(
+            game,
+            Game {
+                my: 0b11111 | 2 << 25,
+                other: 0b11111 | 2 << 25,
+                cards: 0b00011 | 0b01100 << 16,
+                table: 4,
+            }
+        );
+        assert_eq!(game.to_string(), text);
+
+        let text = "...x./.X.../..o../...../O.... tiger eel frog cobra rabbit";
+        assert_eq!(text.parse::<Game>().unwrap().to_string(), text);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!("xxXxx/...../...../ooOoo ox boar horse elephant crab"
+            .parse::<Game>()
+            .is_err());
+        assert!("xxxxx/...../...../...../ooOoo ox boar horse elephant crab"
+            .parse::<Game>()
+            .is_err());
+        assert!("xxXxx/...../...../...../ooOoo ox boar horse elephant"
+            .parse::<Game>()
+            .is_err());
+        assert!("xxXxx/...../...../...../ooOoo ox ox horse elephant crab"
+            .parse::<Game>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_square_name() {
+        assert_eq!(square_name(0), "e1");
+        assert_eq!(square_name(2), "c1");
+        assert_eq!(square_name(24), "a5");
+    }
+}
@@ -15,20 +15,37 @@ fn perft(game: Game, depth: u8) -> usize {
     total
 }
 
+// only moves that do not lose in one are counted and positions with a win in
+// one are not searched further
+#[inline(never)]
+pub(crate) fn perft_evasions(game: Game, depth: u8) -> usize {
+    let evasions = game.evasions();
+    let mut total = 0;
+    for new_game in game
+        .forward()
+        .filter(|new_game| evasions.contains(new_game))
+    {
+        if depth == 1 {
+            total += 1;
+        } else if !new_game.is_win() {
+            total += perft_evasions(new_game, depth - 1);
+        }
+    }
+    total
+}
+
+pub fn perft_evasions_test(depth: u8) -> usize {
+    perft_evasions(Game::initial([0, 1, 2, 3, 4]), depth)
+}
+
 pub fn perft_test(depth: u8) -> usize {
-    const TEST_GAME: Game = Game {
-        my: 0b11111 | 2 << 25,
-        other: 0b11111 | 2 << 25,
-        cards: 0b00011 | 0b01100 << 16,
-        table: 4,
-    };
-
-    perft(TEST_GAME, depth)
+    perft(Game::initial([0, 1, 2, 3, 4]), depth)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::random::Generator;
 
     #[test]
     fn test_perft() {
@@ -40,4 +57,45 @@ mod tests {
         assert_eq!(perft_test(5), 487780);
         assert_eq!(perft_test(6), 7748422);
     }
+
+    // checks the win in one and evasion shortcuts against full generation
+    fn check_threats(game: Game, depth: u8) {
+        if game.is_loss() {
+            return;
+        }
+        assert_eq!(game.is_win(), game.forward().any(|g| g.is_loss()));
+        if depth == 0 || game.is_win() {
+            return;
+        }
+        assert_eq!(
+            game.is_threatened(),
+            game.swap().forward().any(|g| g.is_loss())
+        );
+        let evasions = game.evasions();
+        for (i, new_game) in game.forward().enumerate() {
+            assert_eq!(game.move_index(new_game), i);
+            assert_eq!(evasions.contains(&new_game), !new_game.is_win());
+            check_threats(new_game, depth - 1);
+        }
+    }
+
+    #[test]
+    fn test_threats() {
+        check_threats(Game::initial([0, 1, 2, 3, 4]), 5);
+        let mut generator = Generator::new(1);
+        for _ in 0..50 {
+            check_threats(generator.playout(10), 3);
+            check_threats(generator.endgame(3), 3);
+        }
+    }
+
+    #[test]
+    fn test_perft_evasions() {
+        assert_eq!(perft_evasions_test(1), 10);
+        assert_eq!(perft_evasions_test(2), 130);
+        assert_eq!(perft_evasions_test(3), 1969);
+        assert_eq!(perft_evasions_test(4), 27185);
+        assert_eq!(perft_evasions_test(5), 446502);
+        assert_eq!(perft_evasions_test(6), 6639469);
+    }
 }
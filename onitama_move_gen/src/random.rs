@@ -0,0 +1,106 @@
+use fastrand::Rng;
+
+use crate::gen::Game;
+
+// random numbers, deals and positions, the same seed always gives the same
+// sequence so tests, fuzzing and benchmarks can be repeated
+pub struct Generator(Rng);
+
+// a different sequence every run
+impl Default for Generator {
+    fn default() -> Self {
+        Generator(Rng::new())
+    }
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Generator(Rng::with_seed(seed))
+    }
+
+    // a number in 0..n
+    pub fn below(&mut self, n: usize) -> usize {
+        self.0.usize(..n)
+    }
+
+    // the seed of another generator
+    pub fn seed(&mut self) -> u64 {
+        self.0.u64(..)
+    }
+
+    // five different cards in the order of `Game::initial`
+    pub fn deal(&mut self) -> [u32; 5] {
+        let mut cards: Vec<u32> = (0..16).collect();
+        self.0.shuffle(&mut cards);
+        [cards[0], cards[1], cards[2], cards[3], cards[4]]
+    }
+
+    // the position after at most `plies` random moves from the start of a random
+    // deal, moves that end the game are never taken
+    pub fn playout(&mut self, plies: usize) -> Game {
+        let mut game = Game::initial(self.deal());
+        for _ in 0..plies {
+            let moves: Vec<Game> = game.forward().filter(|g| !g.is_loss()).collect();
+            if moves.is_empty() {
+                break;
+            }
+            game = moves[self.0.usize(..moves.len())];
+        }
+        game
+    }
+
+    // a position with `pieces` pieces for both players, kings included, on random
+    // squares with a random deal, neither king is captured or on the temple
+    pub fn endgame(&mut self, pieces: usize) -> Game {
+        assert!((1..=5).contains(&pieces));
+        loop {
+            let mut squares: Vec<u32> = (0..25).collect();
+            self.0.shuffle(&mut squares);
+            let (my, other) = squares[..2 * pieces].split_at(pieces);
+            let deal = self.deal();
+            // `other` is from the point of view of the player to move, the kings
+            // are the first squares
+            let game = Game {
+                my: my.iter().fold(my[0] << 25, |bits, pos| bits | 1 << pos),
+                other: other
+                    .iter()
+                    .fold((24 - other[0]) << 25, |bits, pos| bits | 1 << (24 - pos)),
+                cards: 1 << deal[0] | 1 << deal[1] | (1 << deal[2] | 1 << deal[3]) << 16,
+                table: deal[4],
+            };
+            if !game.is_loss() && !game.is_other_loss() {
+                return game;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generator() {
+        let mut a = Generator::new(7);
+        let mut b = Generator::new(7);
+        for _ in 0..100 {
+            let deal = a.deal();
+            assert_eq!(deal, b.deal());
+            let all = deal.iter().fold(0u32, |all, card| all | 1 << card);
+            assert_eq!((all.count_ones(), all >> 16), (5, 0));
+
+            let game = a.playout(20);
+            assert_eq!(game, b.playout(20));
+            assert!(!game.is_loss() && !game.is_other_loss());
+
+            for pieces in 1..=5 {
+                let game = a.endgame(pieces);
+                assert_eq!(game, b.endgame(pieces));
+                assert!(!game.is_loss() && !game.is_other_loss());
+                assert_eq!(game.count_pieces(), pieces);
+                assert_eq!(game.swap().count_pieces(), pieces);
+                assert_eq!(game.my & game.other.reverse_bits() >> 7, 0);
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    cmp::{max, min, Ordering},
+    fmt::{Debug, Display},
+};
+
+use crate::eval::Eval;
+
+// search values, ordered from the point of view of the player to move:
+// - a proven win or loss in n plies is near `MATE`
+// - a tablebase result for a position with some pieces left out is near `TABLE`
+// - everything else is a heuristic value below `HEURISTIC`
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Default)]
+#[repr(transparent)]
+pub struct Score(pub i16);
+
+pub const MATE: i16 = 30000;
+pub const TABLE: i16 = 20000;
+pub const HEURISTIC: i16 = 10000;
+// no game or search is longer than this, which keeps the ranges apart
+pub const MAX_PLIES: i16 = 1000;
+
+impl Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_win() {
+            write!(f, "Win: {}", self.plies())
+        } else if self.is_loss() {
+            write!(f, "Loss: {}", self.plies())
+        } else if self.0 >= TABLE - MAX_PLIES {
+            write!(f, "Table win: {}", TABLE - self.0)
+        } else if self.0 <= MAX_PLIES - TABLE {
+            write!(f, "Table loss: {}", self.0 + TABLE)
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+impl Debug for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self, f)
+    }
+}
+
+impl From<Eval> for Score {
+    #[inline]
+    fn from(eval: Eval) -> Self {
+        match eval.cmp(&Eval::new_tie()) {
+            Ordering::Greater => Score::new_win(eval.plies() as i16),
+            Ordering::Equal => Score::new_tie(),
+            Ordering::Less => Score::new_loss(eval.plies() as i16),
+        }
+    }
+}
+
+impl Score {
+    pub const MIN: Score = Score(-MATE);
+    pub const MAX: Score = Score(MATE);
+
+    #[inline]
+    pub fn new_win(plies: i16) -> Self {
+        debug_assert!((1..MAX_PLIES).contains(&plies));
+        Score(MATE - plies)
+    }
+
+    #[inline]
+    pub fn new_loss(plies: i16) -> Self {
+        debug_assert!((0..MAX_PLIES).contains(&plies));
+        Score(plies - MATE)
+    }
+
+    #[inline]
+    pub fn new_tie() -> Self {
+        Score(0)
+    }
+
+    // a tablebase result that ignores some of the pieces, so it is not proven
+    #[inline]
+    pub fn new_table(eval: Eval) -> Self {
+        match eval.cmp(&Eval::new_tie()) {
+            Ordering::Greater => Score(TABLE - eval.plies() as i16),
+            Ordering::Equal => Score::new_tie(),
+            Ordering::Less => Score(eval.plies() as i16 - TABLE),
+        }
+    }
+
+    #[inline]
+    pub fn new_heuristic(value: i32) -> Self {
+        Score(min(max(value, -(HEURISTIC as i32)), HEURISTIC as i32) as i16)
+    }
+
+    #[inline]
+    pub fn is_win(self) -> bool {
+        self.0 > MATE - MAX_PLIES
+    }
+
+    #[inline]
+    pub fn is_loss(self) -> bool {
+        self.0 < MAX_PLIES - MATE
+    }
+
+    // proven win or loss
+    #[inline]
+    pub fn is_mate(self) -> bool {
+        self.is_win() || self.is_loss()
+    }
+
+    #[inline]
+    pub fn plies(self) -> u16 {
+        if self.is_win() {
+            (MATE - self.0) as u16
+        } else if self.is_loss() {
+            (self.0 + MATE) as u16
+        } else {
+            u16::MAX
+        }
+    }
+
+    // the score of the parent position given the score of a child
+    #[inline]
+    pub fn backward(self) -> Self {
+        if self.is_win() {
+            Score(1 - self.0)
+        } else if self.is_loss() {
+            Score(-self.0 - 1)
+        } else {
+            Score(-self.0)
+        }
+    }
+
+    // the inverse of backward
+    #[inline]
+    pub fn forward(self) -> Self {
+        if self.is_win() {
+            Score(-self.0 - 1)
+        } else if self.is_loss() {
+            Score(1 - self.0)
+        } else {
+            Score(-self.0)
+        }
+    }
+
+    #[inline]
+    pub fn saturating_add(self, value: i16) -> Self {
+        Score(self.0.saturating_add(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_cmp() {
+        assert!(Score::new_win(1) > Score::new_win(3));
+        assert!(Score::new_win(99) > Score::new_table(Eval::new_win(1)));
+        assert!(Score::new_table(Eval::new_win(9)) > Score::new_heuristic(i32::MAX));
+        assert!(Score::new_heuristic(i32::MIN) > Score::new_table(Eval::new_loss(9)));
+        assert!(Score::new_table(Eval::new_loss(0)) > Score::new_loss(99));
+        assert!(Score::new_loss(5) > Score::new_loss(3));
+        assert_eq!(Score::new_loss(0), Score::MIN);
+    }
+
+    #[test]
+    fn test_from_eval() {
+        let mut evals: Vec<Eval> = (-128..=127).map(Eval).collect();
+        evals.sort();
+        for pair in evals.windows(2) {
+            assert!(Score::from(pair[0]) < Score::from(pair[1]));
+            assert!(Score::new_table(pair[0]) < Score::new_table(pair[1]));
+        }
+        for eval in evals {
+            assert_eq!(Score::from(eval).plies() as u8, eval.plies());
+            // the evals of their parents do not fit in an i8
+            if eval != Eval::new_win(127) && eval != Eval::new_loss(127) {
+                assert_eq!(Score::from(eval.backward()), Score::from(eval).backward());
+            }
+        }
+    }
+
+    #[test]
+    fn test_forward_backward() {
+        for i in -TABLE..=TABLE {
+            assert_eq!(Score(i).backward().forward(), Score(i));
+        }
+        for plies in 0..MAX_PLIES - 2 {
+            let loss = Score::new_loss(plies);
+            let win = Score::new_win(plies + 1);
+            assert_eq!(loss.backward().forward(), loss);
+            assert_eq!(win.backward().forward(), win);
+        }
+        assert_eq!(Score::new_win(3).backward(), Score::new_loss(4));
+        assert_eq!(Score::new_loss(4).backward(), Score::new_win(5));
+        assert_eq!(Score(-20).backward(), Score(20));
+    }
+
+    #[test]
+    fn test_score_display() {
+        assert_eq!("Win: 3", format!("{}", Score::new_win(3)));
+        assert_eq!("Loss: 0", format!("{}", Score::new_loss(0)));
+        assert_eq!(
+            "Table win: 5",
+            format!("{}", Score::new_table(Eval::new_win(3)))
+        );
+        assert_eq!("-12", format!("{}", Score(-12)));
+    }
+}
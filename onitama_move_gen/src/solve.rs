@@ -0,0 +1,698 @@
+use std::{
+    cmp::min,
+    collections::{HashMap, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use bitintr::{Pdep, Pext, Popcnt};
+use rayon::prelude::*;
+
+use crate::{
+    gen::{Game, My, Other, PIECE_MASK},
+    ops::BitIter,
+    tablebase::{card_config, compress_cards},
+};
+
+// Win/draw/loss retrograde analysis of all positions with up to `max_pawns` pawns per side
+// for a single card deal. Every bit set lives in a file and only a few pages are kept in
+// memory, with all pawns (4) every set takes about 121 GiB.
+
+const PAGE_WORDS: usize = 1 << 14;
+const PAGE_BITS: u64 = PAGE_WORDS as u64 * 64;
+const PAGE_BYTES: u64 = PAGE_WORDS as u64 * 8;
+const CACHE_PAGES: usize = 256;
+
+struct Page {
+    words: Box<[u64]>,
+    dirty: bool,
+}
+
+pub struct BitFile {
+    file: File,
+    len: u64,
+    pages: HashMap<u64, Page>,
+    order: VecDeque<u64>,
+    capacity: usize,
+}
+
+impl BitFile {
+    pub fn open(path: &Path, len: u64, capacity: usize) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let bytes = page_count(len) * PAGE_BYTES;
+        if file.metadata()?.len() != bytes {
+            file.set_len(bytes)?;
+        }
+        Ok(Self {
+            file,
+            len,
+            pages: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+        })
+    }
+
+    pub fn create(path: &Path, len: u64, capacity: usize) -> io::Result<Self> {
+        File::create(path)?;
+        Self::open(path, len, capacity)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&mut self, index: u64) -> io::Result<bool> {
+        let word = self.page(index / PAGE_BITS)?.words[(index % PAGE_BITS / 64) as usize];
+        Ok(word & 1 << (index % 64) != 0)
+    }
+
+    // returns whether the bit was set before
+    pub fn set(&mut self, index: u64) -> io::Result<bool> {
+        let page = self.page(index / PAGE_BITS)?;
+        let word = &mut page.words[(index % PAGE_BITS / 64) as usize];
+        let old = *word & 1 << (index % 64) != 0;
+        *word |= 1 << (index % 64);
+        page.dirty |= !old;
+        Ok(old)
+    }
+
+    // replaces every bit of `page`
+    pub fn set_page(&mut self, page: u64, words: Box<[u64]>) -> io::Result<()> {
+        let data = self.page(page)?;
+        data.words = words;
+        data.dirty = true;
+        Ok(())
+    }
+
+    pub fn count(&mut self) -> io::Result<u64> {
+        let mut total = 0;
+        self.for_each_set(|_| {
+            total += 1;
+            Ok(())
+        })?;
+        Ok(total)
+    }
+
+    pub fn for_each_set(&mut self, mut f: impl FnMut(u64) -> io::Result<()>) -> io::Result<()> {
+        for page in 0..page_count(self.len) {
+            let words = self.page(page)?.words.clone();
+            for (i, &word) in words.iter().enumerate() {
+                for bit in BitIter(word as u32).chain(BitIter((word >> 32) as u32).map(|b| b + 32))
+                {
+                    f(page * PAGE_BITS + i as u64 * 64 + bit as u64)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        for (&page, data) in self.pages.iter_mut() {
+            if data.dirty {
+                write_page(&mut self.file, page, &data.words)?;
+                data.dirty = false;
+            }
+        }
+        self.file.sync_data()
+    }
+
+    fn page(&mut self, page: u64) -> io::Result<&mut Page> {
+        if !self.pages.contains_key(&page) {
+            if self.pages.len() >= self.capacity {
+                let old = self.order.pop_front().unwrap();
+                let data = self.pages.remove(&old).unwrap();
+                if data.dirty {
+                    write_page(&mut self.file, old, &data.words)?;
+                }
+            }
+            let words = read_page(&mut self.file, page)?;
+            self.pages.insert(
+                page,
+                Page {
+                    words,
+                    dirty: false,
+                },
+            );
+            self.order.push_back(page);
+        }
+        Ok(self.pages.get_mut(&page).unwrap())
+    }
+}
+
+// the pages that `len` bits need, `PAGE_BITS` is a power of two
+fn page_count(len: u64) -> u64 {
+    (len + PAGE_BITS - 1) >> PAGE_BITS.trailing_zeros()
+}
+
+fn read_page(file: &mut File, page: u64) -> io::Result<Box<[u64]>> {
+    let mut bytes = vec![0; PAGE_BYTES as usize];
+    file.seek(SeekFrom::Start(page * PAGE_BYTES))?;
+    file.read_exact(&mut bytes)?;
+    Ok(bytes
+        .chunks_exact(8)
+        .map(|chunk| {
+            u64::from_le_bytes([
+                chunk[0], chunk[1], chunk[2], chunk[3], chunk[4], chunk[5], chunk[6], chunk[7],
+            ])
+        })
+        .collect())
+}
+
+fn write_page(file: &mut File, page: u64, words: &[u64]) -> io::Result<()> {
+    let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    file.seek(SeekFrom::Start(page * PAGE_BYTES))?;
+    file.write_all(&bytes)
+}
+
+// positions are indexed by cards, both kings and then the pawns on the 23 squares left
+pub struct Indexer {
+    max_pawns: u32,
+    configs: [(u32, u32); 30],
+    binomial: [[u64; 5]; 26],
+    offsets: Vec<u64>,
+    pawns: u64,
+}
+
+impl Indexer {
+    pub fn new(cards: [u32; 5], max_pawns: u32) -> Self {
+        assert!(max_pawns <= 4);
+        let mut configs = [(0, 0); 30];
+        for &(cards, table) in &card_config(cards) {
+            configs[compress_cards(cards, table) as usize] = (cards, table);
+        }
+
+        let mut binomial = [[0; 5]; 26];
+        for n in 0..26 {
+            binomial[n][0] = 1;
+            for k in 1..5 {
+                binomial[n][k] = if n == 0 {
+                    0
+                } else {
+                    binomial[n - 1][k - 1] + binomial[n - 1][k]
+                };
+            }
+        }
+
+        let mut offsets = Vec::new();
+        let mut pawns = 0;
+        for my in 0..=max_pawns as usize {
+            for other in 0..=max_pawns as usize {
+                offsets.push(pawns);
+                pawns += binomial[23][my] * binomial[23 - my][other];
+            }
+        }
+
+        Self {
+            max_pawns,
+            configs,
+            binomial,
+            offsets,
+            pawns,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        30 * 25 * 24 * self.pawns
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, game: Game) -> bool {
+        (game.my & PIECE_MASK).popcnt() <= self.max_pawns + 1
+            && (game.other & PIECE_MASK).popcnt() <= self.max_pawns + 1
+    }
+
+    pub fn index(&self, game: Game) -> u64 {
+        debug_assert!(self.contains(game));
+        let cards = compress_cards(game.cards, game.table) as u64;
+        let my_king = game.king::<My>();
+        let other_king = 24 - game.king::<Other>();
+        let kings = my_king * 24 + other_king - (other_king > my_king) as u32;
+
+        let free = PIECE_MASK & !(1 << my_king) & !(1 << other_king);
+        let my = (game.my & PIECE_MASK).pext(free);
+        let other = ((game.other & PIECE_MASK).reverse_bits() >> 7).pext(free & !game.my);
+        let (my_count, other_count) = (my.popcnt() as usize, other.popcnt() as usize);
+
+        let pawns = self.offsets[my_count * (self.max_pawns as usize + 1) + other_count]
+            + self.rank(my) * self.binomial[23 - my_count][other_count]
+            + self.rank(other);
+        (cards * 600 + kings as u64) * self.pawns + pawns
+    }
+
+    pub fn decode(&self, index: u64) -> Game {
+        let mut pawns = index % self.pawns;
+        let kings = (index / self.pawns % 600) as u32;
+        let (cards, table) = self.configs[(index / self.pawns / 600) as usize];
+
+        let my_king = kings / 24;
+        let mut other_king = kings % 24;
+        if other_king >= my_king {
+            other_king += 1;
+        }
+
+        let i = self
+            .offsets
+            .iter()
+            .rposition(|&offset| offset <= pawns)
+            .unwrap();
+        let (my_count, other_count) = (
+            i / (self.max_pawns as usize + 1),
+            i % (self.max_pawns as usize + 1),
+        );
+        pawns -= self.offsets[i];
+        let per_my = self.binomial[23 - my_count][other_count];
+        let my = self.unrank(pawns / per_my, my_count);
+        let other = self.unrank(pawns % per_my, other_count);
+
+        let free = PIECE_MASK & !(1 << my_king) & !(1 << other_king);
+        let my = my.pdep(free);
+        let other = other.pdep(free & !my);
+        Game {
+            my: my | 1 << my_king | my_king << 25,
+            other: (other | 1 << other_king).reverse_bits() >> 7 | (24 - other_king) << 25,
+            cards,
+            table,
+        }
+    }
+
+    // colexicographic rank among the sets with the same number of bits
+    fn rank(&self, bits: u32) -> u64 {
+        BitIter(bits)
+            .enumerate()
+            .map(|(i, pos)| self.binomial[pos as usize][i + 1])
+            .sum()
+    }
+
+    fn unrank(&self, mut rank: u64, count: usize) -> u32 {
+        let mut bits = 0;
+        for k in (1..=count).rev() {
+            let mut pos = k - 1;
+            while self.binomial[pos + 1][k] <= rank {
+                pos += 1;
+            }
+            rank -= self.binomial[pos][k];
+            bits |= 1 << pos;
+        }
+        bits
+    }
+
+    // previous positions, including the ones where a pawn was taken if it fits
+    fn for_each_prev(
+        &self,
+        game: Game,
+        mut f: impl FnMut(Game) -> io::Result<()>,
+    ) -> io::Result<()> {
+        for (mut prev_game, take) in game.backward() {
+            if prev_game.is_loss() || prev_game.is_other_loss() {
+                continue;
+            }
+            f(prev_game)?;
+            if (prev_game.other & PIECE_MASK).popcnt() <= self.max_pawns {
+                prev_game.other |= take;
+                f(prev_game)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Search,
+    Merge,
+    Done,
+}
+
+pub struct Solver {
+    dir: PathBuf,
+    meta: String,
+    indexer: Indexer,
+    win: BitFile,
+    loss: BitFile,
+    iteration: u32,
+    phase: Phase,
+}
+
+impl Solver {
+    // continues from the last checkpoint in `dir` if there is one
+    pub fn open(dir: impl AsRef<Path>, cards: [u32; 5], max_pawns: u32) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let indexer = Indexer::new(cards, max_pawns);
+        let len = indexer.len();
+        let meta = format!("{:?} {}", cards, max_pawns);
+
+        let checkpoint = fs::read_to_string(dir.join("checkpoint")).ok();
+        let mut solver = Self {
+            win: BitFile::open(&dir.join("win.bits"), len, CACHE_PAGES)?,
+            loss: BitFile::open(&dir.join("loss.bits"), len, CACHE_PAGES)?,
+            dir,
+            meta,
+            indexer,
+            iteration: 0,
+            phase: Phase::Search,
+        };
+
+        match checkpoint {
+            Some(checkpoint) => solver.load_checkpoint(&checkpoint)?,
+            None => solver.init()?,
+        }
+        if solver.phase == Phase::Merge {
+            solver.merge()?;
+        }
+        Ok(solver)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.phase == Phase::Done
+    }
+
+    pub fn iteration(&self) -> u32 {
+        self.iteration
+    }
+
+    // runs one iteration and returns the number of new wins and losses
+    pub fn step(&mut self) -> io::Result<(u64, u64)> {
+        if self.is_done() {
+            return Ok((0, 0));
+        }
+        let len = self.indexer.len();
+        let indexer = &self.indexer;
+        let mut new_win = self.frontier("win", self.iteration)?;
+        let mut new_loss = self.frontier("loss", self.iteration)?;
+        let next_win_path = self.frontier_path("win", self.iteration + 1);
+        let next_loss_path = self.frontier_path("loss", self.iteration + 1);
+        let mut next_win = BitFile::create(&next_win_path, len, CACHE_PAGES)?;
+        let mut next_loss = BitFile::create(&next_loss_path, len, CACHE_PAGES)?;
+
+        // everything that can move to a new loss is a win
+        let win = &mut self.win;
+        new_loss.for_each_set(|index| {
+            indexer.for_each_prev(indexer.decode(index), |prev_game| {
+                let prev = indexer.index(prev_game);
+                if !win.get(prev)? {
+                    next_win.set(prev)?;
+                }
+                Ok(())
+            })
+        })?;
+
+        // everything that can only move to wins is a loss
+        let (win, loss) = (&mut self.win, &mut self.loss);
+        new_win.for_each_set(|index| {
+            indexer.for_each_prev(indexer.decode(index), |prev_game| {
+                let prev = indexer.index(prev_game);
+                if win.get(prev)? || loss.get(prev)? {
+                    return Ok(());
+                }
+                for new_game in prev_game.forward() {
+                    if new_game.is_loss() || !win.get(indexer.index(new_game))? {
+                        return Ok(());
+                    }
+                }
+                next_loss.set(prev)?;
+                Ok(())
+            })
+        })?;
+
+        next_win.flush()?;
+        next_loss.flush()?;
+        let counts = (next_win.count()?, next_loss.count()?);
+        self.iteration += 1;
+        self.phase = Phase::Merge;
+        self.save_checkpoint()?;
+        self.merge()?;
+
+        if counts == (0, 0) {
+            self.phase = Phase::Done;
+            self.save_checkpoint()?;
+        }
+        Ok(counts)
+    }
+
+    pub fn solve(&mut self, mut progress: impl FnMut(u32, (u64, u64))) -> io::Result<()> {
+        while !self.is_done() {
+            let counts = self.step()?;
+            progress(self.iteration, counts);
+        }
+        Ok(())
+    }
+
+    // the result for the player to move, positions that are not resolved (yet) are draws
+    pub fn outcome(&mut self, game: Game) -> io::Result<Outcome> {
+        assert!(self.indexer.contains(game));
+        if game.is_loss() {
+            return Ok(Outcome::Loss);
+        }
+        let index = self.indexer.index(game);
+        Ok(if self.win.get(index)? {
+            Outcome::Win
+        } else if self.loss.get(index)? {
+            Outcome::Loss
+        } else {
+            Outcome::Draw
+        })
+    }
+
+    // terminal losses and wins in one
+    fn init(&mut self) -> io::Result<()> {
+        let len = self.indexer.len();
+        let mut new_win = BitFile::create(&self.frontier_path("win", 0), len, CACHE_PAGES)?;
+        let mut new_loss = BitFile::create(&self.frontier_path("loss", 0), len, CACHE_PAGES)?;
+        self.win = BitFile::create(&self.dir.join("win.bits"), len, CACHE_PAGES)?;
+        self.loss = BitFile::create(&self.dir.join("loss.bits"), len, CACHE_PAGES)?;
+
+        // the pages are filled in parallel, one batch of them at a time
+        let pages = page_count(len);
+        let indexer = &self.indexer;
+        for batch in (0..pages).step_by(CACHE_PAGES) {
+            let words: Vec<_> = (batch..min(batch + CACHE_PAGES as u64, pages))
+                .into_par_iter()
+                .map(|page| {
+                    let mut win = vec![0; PAGE_WORDS].into_boxed_slice();
+                    let mut loss = vec![0; PAGE_WORDS].into_boxed_slice();
+                    let start = page * PAGE_BITS;
+                    for index in start..min(start + PAGE_BITS, len) {
+                        let game = indexer.decode(index);
+                        let (word, bit) = (((index - start) / 64) as usize, 1 << (index % 64));
+                        if game.is_loss() {
+                            loss[word] |= bit;
+                        } else if !game.is_other_loss()
+                            && game.forward().any(|new_game| new_game.is_loss())
+                        {
+                            win[word] |= bit;
+                        }
+                    }
+                    (win, loss)
+                })
+                .collect();
+            for (page, (win, loss)) in (batch..).zip(words) {
+                self.win.set_page(page, win.clone())?;
+                new_win.set_page(page, win)?;
+                self.loss.set_page(page, loss.clone())?;
+                new_loss.set_page(page, loss)?;
+            }
+        }
+        new_win.flush()?;
+        new_loss.flush()?;
+        self.win.flush()?;
+        self.loss.flush()?;
+        self.iteration = 0;
+        self.phase = Phase::Search;
+        self.save_checkpoint()
+    }
+
+    // adds the last frontier to the results, this can be repeated safely
+    fn merge(&mut self) -> io::Result<()> {
+        let mut new_win = self.frontier("win", self.iteration)?;
+        let mut new_loss = self.frontier("loss", self.iteration)?;
+        let (win, loss) = (&mut self.win, &mut self.loss);
+        new_win.for_each_set(|index| win.set(index).map(drop))?;
+        new_loss.for_each_set(|index| loss.set(index).map(drop))?;
+        self.win.flush()?;
+        self.loss.flush()?;
+        self.phase = Phase::Search;
+        self.save_checkpoint()?;
+
+        for name in &["win", "loss"] {
+            match fs::remove_file(self.frontier_path(name, self.iteration.wrapping_sub(1))) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn frontier_path(&self, name: &str, iteration: u32) -> PathBuf {
+        self.dir.join(format!("{}_{}.bits", name, iteration))
+    }
+
+    fn frontier(&self, name: &str, iteration: u32) -> io::Result<BitFile> {
+        BitFile::open(
+            &self.frontier_path(name, iteration),
+            self.indexer.len(),
+            CACHE_PAGES,
+        )
+    }
+
+    fn save_checkpoint(&self) -> io::Result<()> {
+        let phase = match self.phase {
+            Phase::Search => "search",
+            Phase::Merge => "merge",
+            Phase::Done => "done",
+        };
+        let tmp = self.dir.join("checkpoint.tmp");
+        fs::write(
+            &tmp,
+            format!("{}\n{} {}\n", self.meta, self.iteration, phase),
+        )?;
+        fs::rename(tmp, self.dir.join("checkpoint"))
+    }
+
+    fn load_checkpoint(&mut self, checkpoint: &str) -> io::Result<()> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        let mut lines = checkpoint.lines();
+        if lines.next() != Some(self.meta.as_str()) {
+            return Err(invalid("checkpoint is for different cards or pawns"));
+        }
+        let mut state = lines
+            .next()
+            .ok_or_else(|| invalid("missing state"))?
+            .split(' ');
+        self.iteration = state
+            .next()
+            .and_then(|iteration| iteration.parse().ok())
+            .ok_or_else(|| invalid("invalid iteration"))?;
+        self.phase = match state.next() {
+            Some("search") => Phase::Search,
+            Some("merge") => Phase::Merge,
+            Some("done") => Phase::Done,
+            _ => return Err(invalid("invalid phase")),
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use super::{BitFile, Indexer, Outcome, Solver, PAGE_BITS};
+    use crate::{eval::Eval, tablebase::TableBase};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("onitama_solve_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_bit_file() {
+        let dir = temp_dir("bit_file");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.bits");
+        let len = PAGE_BITS * 5 + 3;
+        let indices = [0, 1, 63, 64, PAGE_BITS - 1, PAGE_BITS * 3 + 7, len - 1];
+
+        let mut bits = BitFile::create(&path, len, 2).unwrap();
+        for &index in &indices {
+            assert!(!bits.set(index).unwrap());
+            assert!(bits.set(index).unwrap());
+        }
+        bits.flush().unwrap();
+
+        let mut bits = BitFile::open(&path, len, 1).unwrap();
+        let mut found = Vec::new();
+        bits.for_each_set(|index| {
+            found.push(index);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(found, indices);
+        assert!(!bits.get(2).unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_index() {
+        for max_pawns in 0..=2 {
+            let indexer = Indexer::new([6, 13, 15, 12, 9], max_pawns);
+            let step = if max_pawns == 2 { 997 } else { 1 };
+            for index in (0..indexer.len()).step_by(step) {
+                assert_eq!(indexer.index(indexer.decode(index)), index);
+            }
+        }
+        assert_eq!(Indexer::new([0, 1, 2, 3, 4], 4).len(), 1038540546000);
+    }
+
+    #[test]
+    fn test_matches_tablebase() {
+        let cards = [6, 13, 15, 12, 9];
+        let dir = temp_dir("tablebase");
+        let mut solver = Solver::open(&dir, cards, 1).unwrap();
+        solver.solve(|_, _| {}).unwrap();
+        let table = TableBase::new(cards);
+
+        let indexer = Indexer::new(cards, 1);
+        for index in 0..indexer.len() {
+            let game = indexer.decode(index);
+            if game.is_other_loss() {
+                continue;
+            }
+            let expected = match table.probe(game).unwrap() {
+                eval if eval > Eval::new_tie() => Outcome::Win,
+                eval if eval < Eval::new_tie() => Outcome::Loss,
+                _ => Outcome::Draw,
+            };
+            assert_eq!(solver.outcome(game).unwrap(), expected, "{}", game);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let cards = [0, 1, 2, 3, 4];
+        let dir = temp_dir("resumed");
+        let mut solver = Solver::open(&dir, cards, 1).unwrap();
+        solver.step().unwrap();
+        solver.step().unwrap();
+        drop(solver);
+        let mut resumed = Solver::open(&dir, cards, 1).unwrap();
+        assert_eq!(resumed.iteration(), 2);
+        resumed.solve(|_, _| {}).unwrap();
+
+        let full_dir = temp_dir("full");
+        let mut full = Solver::open(&full_dir, cards, 1).unwrap();
+        full.solve(|_, _| {}).unwrap();
+        assert_eq!(full.iteration(), resumed.iteration());
+
+        let indexer = Indexer::new(cards, 1);
+        for index in 0..indexer.len() {
+            let game = indexer.decode(index);
+            assert_eq!(resumed.outcome(game).unwrap(), full.outcome(game).unwrap());
+        }
+        assert!(Solver::open(&dir, cards, 2).is_err());
+        fs::remove_dir_all(dir).unwrap();
+        fs::remove_dir_all(full_dir).unwrap();
+    }
+}
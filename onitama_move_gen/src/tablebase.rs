@@ -1,21 +1,50 @@
 use std::{
     alloc::{alloc_zeroed, Layout},
-    cmp::{max, min},
-    mem::take,
+    cmp::{max, min, Ordering},
+    fmt::Display,
+    mem::{size_of, take},
     ops::{Index, IndexMut},
+    slice,
+    sync::atomic::{AtomicI8, Ordering::Relaxed},
 };
 
 use bitintr::{Andn, Pext, Popcnt};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 
 use crate::{
+    backend::MoveGen,
     eval::Eval,
     gen::{Game, PIECE_MASK},
     ops::{BitIter, CardIter},
+    score::Score,
 };
 
 type TableData = [[[[[Eval; 26]; 26]; 25]; 25]; 30];
 pub struct TableBase(TableData);
 
+const TABLE_SIZE: usize = size_of::<TableData>();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+    pub game: Game,
+    pub eval: Eval,
+    pub reason: &'static str,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({}): {}", self.game, self.eval, self.reason)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    // all terminal positions and the wins in one are stored
+    Initialized,
+    // this many possible losses are resolved in the next wave
+    Wave(usize),
+}
+
 impl TableBase {
     pub fn empty() -> Box<Self> {
         unsafe {
@@ -25,72 +54,98 @@ impl TableBase {
     }
 
     pub fn new(cards: [u32; 5]) -> Box<Self> {
+        Self::with_progress(cards, |_| {})
+    }
+
+    pub fn with_progress(cards: [u32; 5], progress: impl FnMut(Progress)) -> Box<Self> {
+        Self::with_backend::<Game>(cards, progress)
+    }
+
+    // every wave is resolved in parallel against the table as it was at the start of the wave,
+    // wins are then propagated with atomic updates, all moves and moves back come from `M`
+    pub fn with_backend<M: MoveGen>(
+        cards: [u32; 5],
+        mut progress: impl FnMut(Progress),
+    ) -> Box<Self> {
+        let mut table = Self::empty();
+        let cells = table.atomic();
+        let cards = card_config(cards);
+
+        let mut queue: Vec<Game> = (0..25)
+            .into_par_iter()
+            .flat_map_iter(|other_king| {
+                let mut queue = Vec::new();
+                for_each_terminal(&cards, other_king, |game| {
+                    cells.set(game, Eval::new_loss(0));
+                    for_each_prev::<M>(game, |prev_game| {
+                        if !prev_game.is_other_loss() {
+                            cells.check_win::<M>(&mut queue, prev_game, Eval::new_win(1));
+                        }
+                    });
+                });
+                queue
+            })
+            .collect();
+        progress(Progress::Initialized);
+
+        while !queue.is_empty() {
+            progress(Progress::Wave(queue.len()));
+            let evals: Vec<(Game, Eval)> = queue
+                .into_par_iter()
+                .filter(|&game| cells.get(game) == Eval::new_loss(0))
+                .map(|game| (game, loss_eval::<M>(game, |new_game| cells.get(new_game))))
+                .collect();
+            evals
+                .par_iter()
+                .for_each(|&(game, eval)| cells.set(game, eval));
+            queue = evals
+                .into_par_iter()
+                .filter(|&(_, eval)| eval < Eval::new_tie())
+                .flat_map_iter(|(game, eval)| {
+                    let mut queue = Vec::new();
+                    let prev_eval = eval.backward();
+                    for_each_prev::<M>(game, |prev_game| {
+                        cells.check_win::<M>(&mut queue, prev_game, prev_eval)
+                    });
+                    queue
+                })
+                .collect();
+        }
+
+        table
+    }
+
+    // the same table built one position at a time with `Game`, to check the parallel build
+    pub fn new_serial(cards: [u32; 5], mut progress: impl FnMut(Progress)) -> Box<Self> {
         let mut table = Self::empty();
         let mut queue = Vec::new();
         let cards = card_config(cards);
 
         for other_king in 0..25 {
-            for (my, other) in piece_config(1 << 24 >> other_king) {
-                let full_other = 1 << 24 >> other | 1 << 24 >> other_king;
-                let my_king_iter = if other_king == 22 {
-                    BitIter((1 << 22).andn(PIECE_MASK))
-                } else {
-                    BitIter((1 << 22).andn(full_other))
-                };
-                for my_king in my_king_iter {
-                    for &(cards, center) in &cards {
-                        let game = Game {
-                            cards,
-                            table: center,
-                            my: 1 << my & PIECE_MASK | 1 << my_king & !full_other | my_king << 25,
-                            other: 1 << other & PIECE_MASK | 1 << other_king | other_king << 25,
-                        };
-                        table[game] = Eval::new_loss(0);
-                        for (mut prev_game, take) in game.backward() {
-                            if !prev_game.is_other_loss() {
-                                table.check_win(&mut queue, prev_game, Eval::new_win(1));
-                            }
-                            if (prev_game.other & PIECE_MASK).popcnt() < 2 {
-                                prev_game.other |= take;
-                                if !prev_game.is_other_loss() {
-                                    table.check_win(&mut queue, prev_game, Eval::new_win(1));
-                                }
-                            }
-                        }
+            for_each_terminal(&cards, other_king, |game| {
+                table[game] = Eval::new_loss(0);
+                for_each_prev::<Game>(game, |prev_game| {
+                    if !prev_game.is_other_loss() {
+                        table.check_win(&mut queue, prev_game, Eval::new_win(1));
                     }
-                }
-            }
-            dbg!("done");
+                });
+            });
         }
+        progress(Progress::Initialized);
 
         while !queue.is_empty() {
-            dbg!(queue.len());
+            progress(Progress::Wave(queue.len()));
             for game in take(&mut queue) {
                 if table[game] != Eval::new_loss(0) {
                     continue;
                 }
-                let mut eval = Eval::new_loss(0);
-                for new_game in game.forward() {
-                    if table[new_game] == Eval::new_loss(0) || table[new_game] == Eval::new_tie() {
-                        eval = Eval::new_tie();
-                        break;
-                    } else {
-                        debug_assert!(table[new_game] >= Eval::new_tie());
-                        eval = max(eval, table[new_game].backward());
-                    }
-                }
-                debug_assert!(eval <= Eval::new_tie());
-                debug_assert!(eval != Eval::new_loss(0));
+                let eval = loss_eval::<Game>(game, |new_game| table[new_game]);
                 table[game] = eval;
                 if eval < Eval::new_tie() {
                     let prev_eval = eval.backward();
-                    for (mut prev_game, take) in game.backward() {
-                        table.check_win(&mut queue, prev_game, prev_eval);
-                        if (prev_game.other & PIECE_MASK).popcnt() < 2 {
-                            prev_game.other |= take;
-                            table.check_win(&mut queue, prev_game, prev_eval);
-                        }
-                    }
+                    for_each_prev::<Game>(game, |prev_game| {
+                        table.check_win(&mut queue, prev_game, prev_eval)
+                    });
                 }
             }
         }
@@ -103,13 +158,9 @@ impl TableBase {
         if eval > self[game] {
             self[game] = eval;
             let prev_eval = eval.backward();
-            for (mut prev_game, take) in game.backward() {
-                self.check_loss(queue, prev_game, prev_eval);
-                if (prev_game.other & PIECE_MASK).popcnt() < 2 {
-                    prev_game.other |= take;
-                    self.check_loss(queue, prev_game, prev_eval);
-                }
-            }
+            for_each_prev::<Game>(game, |prev_game| {
+                self.check_loss(queue, prev_game, prev_eval)
+            });
         }
     }
 
@@ -120,8 +171,116 @@ impl TableBase {
         }
     }
 
+    fn as_slice(&self) -> &[Eval] {
+        unsafe { slice::from_raw_parts(self.0.as_ptr() as *const Eval, TABLE_SIZE) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [Eval] {
+        unsafe { slice::from_raw_parts_mut(self.0.as_mut_ptr() as *mut Eval, TABLE_SIZE) }
+    }
+
+    fn atomic(&mut self) -> AtomicTable<'_> {
+        // Eval is a transparent i8, which has the same layout as AtomicI8
+        let slice = self.as_mut_slice();
+        AtomicTable(unsafe {
+            slice::from_raw_parts(slice.as_mut_ptr() as *const AtomicI8, slice.len())
+        })
+    }
+
+    // whether the exact position is stored, at most one pawn per side
+    #[inline]
+    pub fn contains(game: Game) -> bool {
+        (game.my & PIECE_MASK).popcnt() <= 2 && (game.other & PIECE_MASK).popcnt() <= 2
+    }
+
+    #[inline]
+    fn lookup(&self, game: Game) -> Eval {
+        if game.is_loss() {
+            Eval::new_loss(0)
+        } else {
+            self[game]
+        }
+    }
+
+    // the exact result for the player to move, if the position is stored
+    pub fn probe(&self, game: Game) -> Option<Eval> {
+        if Self::contains(game) {
+            Some(self.lookup(game))
+        } else {
+            None
+        }
+    }
+
+    // every legal move in `game.forward()` order, with its result for the player making it
+    pub fn probe_moves(&self, game: Game) -> Option<Vec<(Game, Eval)>> {
+        if !Self::contains(game) || game.is_loss() {
+            return None;
+        }
+        let moves = game
+            .forward()
+            .map(|new_game| (new_game, self.lookup(new_game).backward()))
+            .collect();
+        Some(moves)
+    }
+
+    // picks the fastest win or the slowest loss, together with its index in `game.forward()`
+    pub fn best_move(&self, game: Game) -> Option<(usize, Game)> {
+        if !Self::contains(game) || game.is_loss() {
+            return None;
+        }
+        game.forward()
+            .enumerate()
+            .max_by_key(|&(_, new_game)| self.lookup(new_game).backward())
+    }
+
+    // checks every stored position against the moves it has, `cards` must be the deal
+    // this table was built for
+    pub fn verify(&self, cards: [u32; 5]) -> Vec<Violation> {
+        let configs = card_config(cards);
+        let mut violations = Vec::new();
+        for index in 0..TABLE_SIZE {
+            let game = match decode(&configs, index) {
+                Some(game) if !game.is_loss() && !game.is_other_loss() => game,
+                _ => continue,
+            };
+            let eval = self[game];
+            let mut children = game.forward().map(|new_game| self.lookup(new_game));
+
+            let reason = match eval.cmp(&Eval::new_tie()) {
+                Ordering::Greater if !children.any(|child| child.backward() == eval) => {
+                    "no move to a loss in one step less"
+                }
+                Ordering::Less if children.any(|child| child <= Eval::new_tie()) => {
+                    "a move does not win for the opponent"
+                }
+                Ordering::Equal if children.any(|child| child < Eval::new_tie()) => {
+                    "a move wins for the player to move"
+                }
+                _ => match game
+                    .forward()
+                    .map(|new_game| self.lookup(new_game).backward())
+                    .max()
+                {
+                    Some(best) if best > eval => "a better move exists",
+                    _ => continue,
+                },
+            };
+            violations.push(Violation { game, eval, reason });
+        }
+        violations
+    }
+
+    // number of stored values for every `Eval::plies`
+    pub fn histogram(&self) -> [usize; 256] {
+        let mut counts = [0; 256];
+        for eval in self.as_slice() {
+            counts[eval.plies() as usize] += 1;
+        }
+        counts
+    }
+
     #[inline]
-    pub fn eval(&self, game: Game) -> (bool, i8) {
+    pub fn eval(&self, game: Game) -> (bool, Score) {
         let my_king = game.my.wrapping_shr(25);
         let mut my = game.my & PIECE_MASK ^ 1 << my_king;
         if my == 0 {
@@ -133,7 +292,6 @@ impl TableBase {
             other |= 1 << 25;
         }
 
-        let diff = my.popcnt() as i8 - other.popcnt() as i8;
         let done = my.popcnt() == 1 && other.popcnt() == 1;
 
         let mut max_eval = Eval::new_loss(0);
@@ -158,19 +316,9 @@ impl TableBase {
 
         let done = done || max_eval.0 <= -127 || max_eval.0 == 127;
         let eval = if done {
-            match max_eval.cmp(&Eval::new_tie()) {
-                std::cmp::Ordering::Less => -127,
-                std::cmp::Ordering::Equal => 0,
-                std::cmp::Ordering::Greater => 127,
-            }
+            Score::from(max_eval)
         } else {
-            max_eval.0
-            // min(
-            //     max(max_eval.0.saturating_add(diff.saturating_mul(80)), -126),
-            //     126,
-            // )
-            // Eval(diff.saturating_mul(40))
-            // max_eval
+            Score::new_table(max_eval)
         };
         (done, eval)
     }
@@ -181,44 +329,141 @@ impl Index<Game> for TableBase {
 
     #[inline]
     fn index(&self, game: Game) -> &Self::Output {
-        let cards = compress_cards(game.cards, game.table) as usize;
-        let my_king = game.my.wrapping_shr(25) as usize;
-        let other_king = game.other.wrapping_shr(25) as usize;
-        let my_pieces = compress_pieces(game.my) as usize;
-        let other_pieces = compress_pieces(game.other) as usize;
-
-        unsafe {
-            self.0
-                .get_unchecked(cards)
-                .get_unchecked(my_king)
-                .get_unchecked(other_king)
-                .get_unchecked(my_pieces)
-                .get_unchecked(other_pieces)
-        }
+        unsafe { self.as_slice().get_unchecked(flat_index(game)) }
     }
 }
 
 impl IndexMut<Game> for TableBase {
     fn index_mut(&mut self, game: Game) -> &mut Self::Output {
-        let cards = compress_cards(game.cards, game.table) as usize;
-        let my_king = game.my.wrapping_shr(25) as usize;
-        let other_king = game.other.wrapping_shr(25) as usize;
-        let my_pieces = compress_pieces(game.my) as usize;
-        let other_pieces = compress_pieces(game.other) as usize;
+        unsafe { self.as_mut_slice().get_unchecked_mut(flat_index(game)) }
+    }
+}
 
-        unsafe {
-            self.0
-                .get_unchecked_mut(cards)
-                .get_unchecked_mut(my_king)
-                .get_unchecked_mut(other_king)
-                .get_unchecked_mut(my_pieces)
-                .get_unchecked_mut(other_pieces)
+struct AtomicTable<'a>(&'a [AtomicI8]);
+
+impl AtomicTable<'_> {
+    #[inline]
+    fn get(&self, game: Game) -> Eval {
+        Eval(self.cell(game).load(Relaxed))
+    }
+
+    #[inline]
+    fn set(&self, game: Game, eval: Eval) {
+        self.cell(game).store(eval.0, Relaxed)
+    }
+
+    #[inline]
+    fn cell(&self, game: Game) -> &AtomicI8 {
+        unsafe { self.0.get_unchecked(flat_index(game)) }
+    }
+
+    fn check_win<M: MoveGen>(&self, queue: &mut Vec<Game>, game: Game, eval: Eval) {
+        debug_assert!(eval > Eval::new_tie());
+        if Eval(self.cell(game).fetch_max(eval.0, Relaxed)) < eval {
+            let prev_eval = eval.backward();
+            for_each_prev::<M>(game, |prev_game| {
+                self.check_loss(queue, prev_game, prev_eval)
+            });
+        }
+    }
+
+    fn check_loss(&self, queue: &mut Vec<Game>, game: Game, eval: Eval) {
+        let cell = self.cell(game);
+        let mut current = Eval(cell.load(Relaxed));
+        while eval < current && current <= Eval::new_tie() {
+            match cell.compare_exchange_weak(current.0, Eval::new_loss(0).0, Relaxed, Relaxed) {
+                Ok(_) => return queue.push(game),
+                Err(new) => current = Eval(new),
+            }
+        }
+    }
+}
+
+// all positions where the player to move has lost, with the given king for the other player
+fn for_each_terminal(cards: &[(u32, u32); 30], other_king: u32, mut f: impl FnMut(Game)) {
+    for (my, other) in piece_config(1 << 24 >> other_king) {
+        let full_other = 1 << 24 >> other | 1 << 24 >> other_king;
+        let my_king_iter = if other_king == 22 {
+            BitIter((1 << 22).andn(PIECE_MASK))
+        } else {
+            BitIter((1 << 22).andn(full_other))
+        };
+        for my_king in my_king_iter {
+            for &(cards, center) in cards {
+                f(Game {
+                    cards,
+                    table: center,
+                    my: 1 << my & PIECE_MASK | 1 << my_king & !full_other | my_king << 25,
+                    other: 1 << other & PIECE_MASK | 1 << other_king | other_king << 25,
+                });
+            }
         }
     }
 }
 
+// previous positions, including the ones where a pawn was taken if that fits in the table
 #[inline]
-fn compress_cards(cards: u32, table: u32) -> u32 {
+fn for_each_prev<M: MoveGen>(game: Game, f: impl FnMut(Game)) {
+    M::for_each_unmove(game, (game.my & PIECE_MASK).popcnt() < 2, f);
+}
+
+// the loss for a position that has no move to a tie or a loss (yet)
+#[inline]
+fn loss_eval<M: MoveGen>(game: Game, get: impl Fn(Game) -> Eval) -> Eval {
+    let mut eval = Eval::new_win(1).backward();
+    let done = M::try_for_each_move(game, false, |_, new_game| {
+        let new_eval = get(new_game);
+        if new_eval == Eval::new_loss(0) || new_eval == Eval::new_tie() {
+            return None;
+        }
+        debug_assert!(new_eval >= Eval::new_tie());
+        eval = max(eval, new_eval.backward());
+        Some(())
+    });
+    match done {
+        Some(()) => eval,
+        None => Eval::new_tie(),
+    }
+}
+
+// the position stored at `index`, if the pieces do not overlap
+fn decode(configs: &[(u32, u32); 30], index: usize) -> Option<Game> {
+    let other_pieces = (index % 26) as u32;
+    let my_pieces = (index / 26 % 26) as u32;
+    let other_king = (index / 26 / 26 % 25) as u32;
+    let my_king = (index / 26 / 26 / 25 % 25) as u32;
+    let compressed = (index / 26 / 26 / 25 / 25) as u32;
+    let &(cards, table) = configs
+        .iter()
+        .find(|&&(cards, table)| compress_cards(cards, table) == compressed)?;
+
+    let my = 1 << my_pieces & PIECE_MASK | 1 << my_king;
+    let other = 1 << other_pieces & PIECE_MASK | 1 << other_king;
+    let overlap = my & (other.reverse_bits() >> 7) != 0;
+    if my_pieces == my_king || other_pieces == other_king || overlap {
+        return None;
+    }
+    Some(Game {
+        my: my | my_king << 25,
+        other: other | other_king << 25,
+        cards,
+        table,
+    })
+}
+
+#[inline]
+fn flat_index(game: Game) -> usize {
+    let cards = compress_cards(game.cards, game.table) as usize;
+    let my_king = game.my.wrapping_shr(25) as usize;
+    let other_king = game.other.wrapping_shr(25) as usize;
+    let my_pieces = compress_pieces(game.my) as usize;
+    let other_pieces = compress_pieces(game.other) as usize;
+
+    (((cards * 25 + my_king) * 25 + other_king) * 26 + my_pieces) * 26 + other_pieces
+}
+
+#[inline]
+pub(crate) fn compress_cards(cards: u32, table: u32) -> u32 {
     let combined = cards | cards.wrapping_shr(16);
     let temp = (((1 << table) - 1) & combined).popcnt();
     temp * 6 + ((cards.pext(combined) & 7) - 1)
@@ -269,7 +514,17 @@ fn compress_pieces(my: u32) -> u32 {
 mod tests {
     use std::collections::HashSet;
 
-    use super::{card_config, compress_cards, compress_pieces, piece_config, TableBase};
+    use super::{
+        card_config, compress_cards, compress_pieces, decode, flat_index, piece_config, Progress,
+        TableBase, TABLE_SIZE,
+    };
+    #[cfg(feature = "v2")]
+    use onitama_move_gen_2::{side::Left, state::State};
+
+    use crate::{
+        eval::Eval,
+        gen::{Game, PIECE_MASK},
+    };
 
     #[test]
     fn test_pieces() {
@@ -305,24 +560,130 @@ mod tests {
 
     #[test]
     fn test_tablebase() {
-        let mut counts = [0; 256];
-        let table = TableBase::new([6, 13, 15, 12, 9]);
-        for v in table.0.iter() {
-            for v in v {
-                for v in v {
-                    for v in v {
-                        for v in v {
-                            counts[v.plies() as usize] += 1;
-                        }
-                    }
+        // these are the counts of the table before `verify` existed, the numbers
+        // that were commented out in the old version of this test (1229010 at 0,
+        // 299591 at 7 and 8 at 56) did not match that table either
+        let counts = TableBase::new([6, 13, 15, 12, 9]).histogram();
+        assert_eq!(counts.iter().sum::<usize>(), TABLE_SIZE);
+        assert_eq!(counts[0], 1182570);
+        assert_eq!(counts[1], 3524067);
+        assert_eq!(counts[2], 744866);
+        assert_eq!(counts[7], 302324);
+        assert_eq!(counts[56], 2016);
+        assert_eq!(counts[214], 1);
+        assert_eq!(counts[255], 2106235);
+        assert!(counts[215..255].iter().all(|&c| c == 0));
+        let total_plies: usize = counts[..255].iter().enumerate().map(|(i, c)| i * c).sum();
+        assert_eq!(total_plies, 89316188);
+    }
+
+    #[test]
+    fn test_verify() {
+        for &cards in &[[6, 13, 15, 12, 9], [0, 1, 2, 3, 4], [5, 7, 8, 10, 14]] {
+            let violations = TableBase::new(cards).verify(cards);
+            for violation in violations.iter().take(10) {
+                println!("{}", violation);
+            }
+            assert_eq!(violations.len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let configs = card_config([6, 13, 15, 12, 9]);
+        let mut count = 0;
+        for index in 0..TABLE_SIZE {
+            if let Some(game) = decode(&configs, index) {
+                assert_eq!(flat_index(game), index);
+                count += 1;
+            }
+        }
+        assert_eq!(count, 30 * 25 * 24 * 553);
+    }
+
+    #[test]
+    fn test_parallel() {
+        for &cards in &[[6, 13, 15, 12, 9], [0, 1, 2, 3, 4]] {
+            let mut waves = 0;
+            let serial = TableBase::new_serial(cards, |_| {});
+            let parallel = TableBase::with_progress(cards, |progress| {
+                if let Progress::Wave(_) = progress {
+                    waves += 1
                 }
+            });
+            assert!(waves > 0);
+            let diff = serial
+                .as_slice()
+                .iter()
+                .zip(parallel.as_slice())
+                .position(|(a, b)| a != b);
+            assert_eq!(diff, None);
+        }
+    }
+
+    #[cfg(feature = "v2")]
+    #[test]
+    fn test_backend() {
+        let cards = [6, 13, 15, 12, 9];
+        let table = TableBase::new(cards);
+        let v2 = TableBase::with_backend::<State<Left>>(cards, |_| {});
+        let configs = card_config(cards);
+        let diff = (0..TABLE_SIZE)
+            .filter_map(|index| decode(&configs, index))
+            .filter(|game| !game.is_loss() && !game.is_other_loss())
+            .find(|&game| table[game] != v2[game]);
+        assert_eq!(diff, None);
+    }
+
+    #[test]
+    fn test_best_move() {
+        let table = TableBase::new([6, 13, 15, 12, 9]);
+        let (cards, center) = card_config([6, 13, 15, 12, 9])[0];
+        for (my, other) in piece_config(1 << 2 | 1 << 22) {
+            let mut game = Game {
+                my: 1 << my & PIECE_MASK | 1 << 2 | 2 << 25,
+                other: 1 << other & PIECE_MASK | 1 << 2 | 2 << 25,
+                cards,
+                table: center,
+            };
+            let eval = table.lookup(game);
+            if eval <= Eval::new_tie() {
+                continue;
             }
+            // both sides play perfectly, so the game lasts exactly as long as promised
+            let mut plies = 0;
+            while !game.is_loss() {
+                game = table.best_move(game).unwrap().1;
+                plies += 1;
+            }
+            assert_eq!(plies, eval.plies());
         }
-        // assert_eq!(counts[0], 1229010);
-        // assert_eq!(counts[7], 299591);
-        // assert_eq!(counts[56], 8);
-        for (i, &c) in counts.iter().enumerate() {
-            println!("{}: {}", i, c);
+    }
+
+    #[test]
+    fn test_probe() {
+        let table = TableBase::new([6, 13, 15, 12, 9]);
+        for &(cards, center) in &card_config([6, 13, 15, 12, 9]) {
+            for (my, other) in piece_config(1 << 7 | 1 << 17) {
+                let game = Game {
+                    my: 1 << my & PIECE_MASK | 1 << 7 | 7 << 25,
+                    other: 1 << other & PIECE_MASK | 1 << 7 | 7 << 25,
+                    cards,
+                    table: center,
+                };
+                let moves = table.probe_moves(game).unwrap();
+                let best = moves.iter().map(|&(_, eval)| eval).max();
+                assert_eq!(best, table.probe(game));
+            }
         }
+
+        let full = Game {
+            my: 0b11111 | 2 << 25,
+            other: 0b11111 | 2 << 25,
+            cards: 1 << 6 | 1 << 13 | (1 << 15 | 1 << 12) << 16,
+            table: 9,
+        };
+        assert_eq!(table.probe(full), None);
+        assert_eq!(table.probe_moves(full), None);
     }
 }
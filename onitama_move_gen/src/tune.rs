@@ -0,0 +1,140 @@
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    gen::Game,
+    heuristic::{features, Weights, FEATURES},
+};
+
+// a labeled position, the target is the expected score for the player to move
+// with 1.0 for a win, 0.5 for a draw and 0.0 for a loss
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    pub features: [i32; FEATURES],
+    pub target: f64,
+}
+
+impl Sample {
+    pub fn new(game: Game, target: f64) -> Self {
+        Self {
+            features: features(game),
+            target,
+        }
+    }
+
+    #[inline]
+    fn eval(&self, weights: &[i32; FEATURES]) -> i32 {
+        weights
+            .iter()
+            .zip(self.features.iter())
+            .map(|(w, f)| w * f)
+            .sum()
+    }
+}
+
+#[inline]
+pub fn sigmoid(scale: f64, eval: f64) -> f64 {
+    1.0 / (1.0 + (-scale * eval).exp())
+}
+
+// mean cross entropy between the targets and the predicted scores
+pub fn loss(samples: &[Sample], weights: Weights, scale: f64) -> f64 {
+    let weights = weights.to_array();
+    let total: f64 = samples
+        .par_iter()
+        .map(|sample| {
+            let p = sigmoid(scale, sample.eval(&weights) as f64).clamp(1e-9, 1.0 - 1e-9);
+            -(sample.target * p.ln() + (1.0 - sample.target) * (1.0 - p).ln())
+        })
+        .sum();
+    total / samples.len() as f64
+}
+
+// the scale that maps evaluations to expected scores best, found with a
+// ternary search on its logarithm since the loss is convex in the scale
+pub fn fit_scale(samples: &[Sample], weights: Weights) -> f64 {
+    let (mut lo, mut hi) = ((1e-4f64).ln(), 0f64);
+    for _ in 0..60 {
+        let a = lo + (hi - lo) / 3.0;
+        let b = hi - (hi - lo) / 3.0;
+        if loss(samples, weights, a.exp()) < loss(samples, weights, b.exp()) {
+            hi = b;
+        } else {
+            lo = a;
+        }
+    }
+    ((lo + hi) / 2.0).exp()
+}
+
+// local search over integer weights, a weight is moved by one step at a time
+// for as long as that lowers the loss, the step is doubled while it keeps helping
+pub fn tune(
+    samples: &[Sample],
+    initial: Weights,
+    scale: f64,
+    mut progress: impl FnMut(usize, Weights, f64),
+) -> Weights {
+    let mut best = initial.to_array();
+    let mut best_loss = loss(samples, initial, scale);
+    for iteration in 0.. {
+        let mut improved = false;
+        for i in 0..FEATURES {
+            for &dir in &[1, -1] {
+                let mut step = dir;
+                loop {
+                    let mut values = best;
+                    values[i] += step;
+                    let new_loss = loss(samples, Weights::from_array(values), scale);
+                    if new_loss >= best_loss {
+                        break;
+                    }
+                    best = values;
+                    best_loss = new_loss;
+                    improved = true;
+                    step *= 2;
+                }
+            }
+        }
+        progress(iteration, Weights::from_array(best), best_loss);
+        if !improved {
+            break;
+        }
+    }
+    Weights::from_array(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(weights: Weights, scale: f64) -> Vec<Sample> {
+        let mut games = vec![Game::initial([0, 1, 2, 3, 4])];
+        for _ in 0..3 {
+            games = games.iter().flat_map(Game::forward).collect();
+        }
+        games
+            .into_iter()
+            .map(|game| Sample::new(game, sigmoid(scale, weights.evaluate(game) as f64)))
+            .collect()
+    }
+
+    #[test]
+    fn test_fit_scale() {
+        let samples = samples(Weights::default(), 0.05);
+        let scale = fit_scale(&samples, Weights::default());
+        assert!((scale - 0.05).abs() < 1e-3, "{}", scale);
+    }
+
+    #[test]
+    fn test_tune() {
+        let target = Weights::from_array([20, 2, 5, 3, 1]);
+        let samples = samples(target, 0.05);
+        let initial = Weights::default();
+        let initial_loss = loss(&samples, initial, 0.05);
+        let mut iterations = 0;
+        let tuned = tune(&samples, initial, 0.05, |_, _, _| iterations += 1);
+        assert!(iterations > 1);
+        assert!(loss(&samples, tuned, 0.05) < initial_loss);
+        // nothing can be taken in three plies, so only the material weight is free
+        assert_eq!(tuned.to_array()[1..], target.to_array()[1..]);
+    }
+}
@@ -0,0 +1,173 @@
+use std::mem::swap;
+
+use crate::{
+    card::single_mask, for_each_iter::ForEachIter, forward::BitIter, side::Side, state::State,
+};
+
+// the positions before the last move, which the other player made with the
+// card that is on the table now, when `uncapture` is set the moves that took one
+// of our pawns are generated as well
+pub struct Unmoves<'a, S> {
+    state: &'a mut State<S>,
+    uncapture: bool,
+}
+
+impl<S: Side> State<S> {
+    pub fn unmoves(&mut self, uncapture: bool) -> Unmoves<'_, S> {
+        Unmoves {
+            state: self,
+            uncapture,
+        }
+    }
+
+    // all parameters are indices, `to` is where the piece of the other player is now
+    fn undo_piece<F, R>(&mut self, from: u32, to: u32, mut card: u32, take: bool, mut f: F) -> R
+    where
+        F: for<'a> FnMut(&mut State<S::Other>) -> R,
+        R: std::ops::Try<Output = ()>,
+    {
+        let mut opp_king = self.opp_king();
+        let opp_pawn_change = if to == opp_king {
+            opp_king = from;
+            0
+        } else {
+            1 << from | 1 << to
+        };
+        let my_pawn_change = if take { 1 << to } else { 0 };
+        let opp_card_change = 1 << self.table | 1 << card;
+
+        *S::Other::get_mut(&mut self.pawns) ^= opp_pawn_change;
+        swap(S::Other::get_mut(&mut self.kings), &mut opp_king);
+        *S::get_mut(&mut self.pawns) ^= my_pawn_change;
+        *S::Other::get_mut(&mut self.cards) ^= opp_card_change;
+        swap(&mut self.table, &mut card);
+
+        let res = f(self.flip());
+
+        *S::Other::get_mut(&mut self.pawns) ^= opp_pawn_change;
+        swap(S::Other::get_mut(&mut self.kings), &mut opp_king);
+        *S::get_mut(&mut self.pawns) ^= my_pawn_change;
+        *S::Other::get_mut(&mut self.cards) ^= opp_card_change;
+        swap(&mut self.table, &mut card);
+
+        res
+    }
+}
+
+impl<S: Side> ForEachIter for Unmoves<'_, S> {
+    type Item<'a> = &'a mut State<S::Other>;
+
+    // this does not check that the positions could have been reached, or that the
+    // game was not over already, when neither this position nor the previous one
+    // is a win in one, the previous position generates this one going forward
+    #[inline]
+    fn try_for_each<F, R>(&mut self, mut f: F) -> R
+    where
+        F: for<'a> FnMut(Self::Item<'a>) -> R,
+        R: std::ops::Try<Output = ()>,
+    {
+        let state = &mut *self.state;
+        let opp_all = state.opp_pawns() | 1 << state.opp_king();
+        let empty = !(state.my_pawns() | 1 << state.my_king() | opp_all);
+        // the king can not have been taken and there are only four pawns
+        let uncapture = self.uncapture && state.my_pawns().count_ones() < 4;
+
+        state.opp_cards().try_for_each(|card| {
+            BitIter(opp_all).try_for_each(|to| {
+                // the other player moved like we would move back
+                let from_mask = single_mask::<S>(state.table, to) & empty;
+                BitIter(from_mask).try_for_each(|from| {
+                    state.undo_piece(from, to, card, false, &mut f)?;
+                    if uncapture {
+                        state.undo_piece(from, to, card, true, &mut f)?;
+                    }
+                    R::from_output(())
+                })
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fastrand::Rng;
+
+    use super::*;
+    use crate::side::Left;
+
+    type Key = ((u32, u32), (u32, u32), (u16, u16), u32);
+
+    fn key<S>(state: &State<S>) -> Key {
+        (state.pawns, state.kings, state.cards, state.table)
+    }
+
+    // the player to move can take the king or go to the temple
+    fn is_win<S: Side>(state: &mut State<S>) -> bool {
+        let other = state.flip();
+        other.king_threats() != 0 || other.temple_threatened()
+    }
+
+    fn check<S: Side>(state: &mut State<S>) -> usize {
+        let current = key(state);
+        let my_pawns = state.my_pawns();
+        let mut checked = 0;
+        // every previous position that is not a win in one goes to this one
+        state.unmoves(true).for_each(|prev| {
+            if !is_win(prev) {
+                assert!(
+                    prev.any(|new_state| key(new_state) == current),
+                    "{:?}",
+                    prev
+                );
+                checked += 1;
+            }
+        });
+        // and this position is a previous position of all of its moves
+        state.for_each(|new_state| {
+            assert!(new_state.unmoves(true).any(|prev| key(prev) == current));
+        });
+        assert!(state
+            .unmoves(false)
+            .all(|prev| prev.opp_pawns() == my_pawns));
+        checked
+    }
+
+    fn playout<S: Side>(state: &mut State<S>, depth: u32, rng: &Rng) -> usize {
+        // the moves are only generated when there is no win in one
+        if is_win(state) {
+            return 0;
+        }
+        let mut checked = check(state);
+        let mut count = 0;
+        state.for_each(|_| count += 1);
+        if depth == 0 || count == 0 {
+            return checked;
+        }
+        let pick = rng.usize(..count);
+        let mut index = 0;
+        state.for_each(|new_state| {
+            if index == pick {
+                checked += playout(new_state, depth - 1, rng);
+            }
+            index += 1;
+        });
+        checked
+    }
+
+    #[test]
+    fn test_unmoves() {
+        let rng = Rng::with_seed(1);
+        let mut checked = 0;
+        for i in 0..300 {
+            let mut cards: Vec<u32> = (0..16).collect();
+            rng.shuffle(&mut cards);
+            let mut state = State::<Left> {
+                cards: (1 << cards[0] | 1 << cards[1], 1 << cards[2] | 1 << cards[3]),
+                table: cards[4],
+                ..Default::default()
+            };
+            checked += playout(&mut state, i % 40, &rng);
+        }
+        assert!(checked > 10000);
+    }
+}
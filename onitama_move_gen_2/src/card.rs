@@ -21,10 +21,9 @@ pub(crate) fn single_mask<S: Side>(card: u32, from: u32) -> u32 {
     fn inner<S: Side>(card: u32, from: u32) -> u32 {
         let bitmap = get_bitmap::<S>(card);
         let mut mask = (((bitmap as u64) << from) >> 14) as u32;
-        if card == 0 && from % 6 >= 3 {
-            mask &= BOARD_MASK << 1
-        } else if card == 0 {
-            mask &= BOARD_MASK >> 1
+        // card 0 moves two columns, that can wrap around to the next row
+        if card == 0 && S::get((from % 6 < 2, from % 6 >= 3)) {
+            mask &= S::get((BOARD_MASK >> 1, BOARD_MASK << 1))
         }
         mask & BOARD_MASK
     }
@@ -92,3 +91,24 @@ pub(crate) fn get_bitmap<S: Side>(card: u32) -> u32 {
 const fn reverse_bitmap(board: u32) -> u32 {
     board.reverse_bits() >> 3
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::side::{Left, Right};
+
+    fn check_masks<S: Side>() {
+        for card in 0..16 {
+            for from in (0..30).filter(|from| 1 << from & BOARD_MASK != 0) {
+                assert_eq!(single_mask::<S>(card, from), all_mask::<S>(1 << from, card));
+            }
+        }
+    }
+
+    // the tiger moves two columns, which used to cut off moves for right
+    #[test]
+    fn test_masks() {
+        check_masks::<Left>();
+        check_masks::<Right>();
+    }
+}
@@ -95,15 +95,18 @@ impl<S: Side> State<S> {
         R: std::ops::Try<Output = ()>,
     {
         let opp_pawn_change = self.opp_pawns() & 1 << to;
+        let my_card_change = 1 << self.table | 1 << card;
 
         *S::Other::get_mut(&mut self.pawns) ^= opp_pawn_change;
         swap(S::get_mut(&mut self.kings), &mut to);
+        *S::get_mut(&mut self.cards) ^= my_card_change;
         swap(&mut self.table, &mut card);
 
         let res = f(self.flip());
 
         *S::Other::get_mut(&mut self.pawns) ^= opp_pawn_change;
         swap(S::get_mut(&mut self.kings), &mut to);
+        *S::get_mut(&mut self.cards) ^= my_card_change;
         swap(&mut self.table, &mut card);
 
         res
@@ -146,3 +149,34 @@ impl<S: Side> ForEachIter for State<S> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::side::Left;
+
+    // the card that was used goes to the table and the mover gets the old one
+    fn check_cards<S: Side>(state: &mut State<S>, depth: u8) -> usize {
+        let (cards, table, king) = (S::get(state.cards), state.table, state.my_king());
+        let mut king_moves = 0;
+        state.for_each(|new_state| {
+            assert_eq!(cards & 1 << new_state.table, 1 << new_state.table);
+            assert_eq!(
+                S::get(new_state.cards),
+                cards ^ (1 << table | 1 << new_state.table)
+            );
+            if new_state.opp_king() != king {
+                king_moves += 1;
+            }
+            if depth > 1 {
+                king_moves += check_cards(new_state, depth - 1);
+            }
+        });
+        king_moves
+    }
+
+    #[test]
+    fn test_cards() {
+        assert!(check_cards(&mut State::<Left>::default(), 4) > 0);
+    }
+}
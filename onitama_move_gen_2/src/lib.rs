@@ -4,6 +4,7 @@
 #![feature(unboxed_closures)]
 #![feature(test)]
 
+pub mod backward;
 pub mod card;
 pub mod for_each_iter;
 mod forward;
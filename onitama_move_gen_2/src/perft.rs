@@ -33,17 +33,17 @@ mod tests {
     #[test]
     fn test_perft() {
         assert_eq!(perft_test(1), 10);
-        assert_eq!(perft_test(2), 108);
-        assert_eq!(perft_test(3), 1328);
-        assert_eq!(perft_test(4), 12388);
-        assert_eq!(perft_test(5), 144384);
-        assert_eq!(perft_test(6), 1432826);
+        assert_eq!(perft_test(2), 120);
+        assert_eq!(perft_test(3), 1322);
+        assert_eq!(perft_test(4), 12296);
+        assert_eq!(perft_test(5), 137588);
+        assert_eq!(perft_test(6), 1366216);
     }
 
     #[bench]
     fn bench_perft(b: &mut Bencher) {
         b.iter(|| {
-            assert_eq!(perft_test(6), 1432826);
+            assert_eq!(perft_test(6), 1366216);
         })
     }
 }
@@ -0,0 +1,115 @@
+use std::{cmp::Ordering, rc::Rc};
+
+use onitama_move_gen::{eval::Eval, gen::Game, random::Generator, tablebase::TableBase};
+
+use crate::{
+    bench::SUITE,
+    engine::{Engine, Limits, Player, Search},
+    node::{DrawRule, Driver, Evaluator, Strength},
+};
+
+// games that take longer than this are counted as a draw, even without a draw rule
+const MAX_PLIES: usize = 200;
+
+// the score of the first engine, which moves first, and the total depth each
+// engine reached
+pub fn play(
+    tablebase: &Rc<TableBase>,
+    mut game: Game,
+    engines: &mut [Box<dyn Engine>; 2],
+    draw: DrawRule,
+    millis: u64,
+) -> (f64, [usize; 2]) {
+    for engine in engines.iter_mut() {
+        engine.new_game(tablebase.clone());
+    }
+    let limits = Limits {
+        millis,
+        ..Limits::default()
+    };
+    let mut depths = [0; 2];
+    let mut history = vec![game];
+    for ply in 0..MAX_PLIES {
+        // the result for the player to move
+        let result = if game.is_loss() {
+            Some(0.0)
+        } else if draw.is_draw(&history) {
+            Some(0.5)
+        } else {
+            tablebase
+                .probe(game)
+                .map(|eval| match eval.cmp(&Eval::new_tie()) {
+                    Ordering::Greater => 1.0,
+                    Ordering::Equal => 0.5,
+                    Ordering::Less => 0.0,
+                })
+        };
+        if let Some(result) = result {
+            return (if ply % 2 == 0 { result } else { 1.0 - result }, depths);
+        }
+        let engine = &mut engines[ply % 2];
+        engine.set_position(&history);
+        let result = engine.go(limits, &mut |_| {});
+        depths[ply % 2] += result.depth as usize;
+        game = game.forward().nth(result.index).unwrap();
+        history.push(game);
+    }
+    (0.5, depths)
+}
+
+// usage: onitama match <ms per move> <config> <config>
+// plays every position of the bench suite twice, once with each player moving
+// first, configs are written like "lmr=3/1,king,temple", "none" or "mcts", the
+// strength only handicaps the second player
+pub fn run(
+    args: &[String],
+    evaluator: Evaluator,
+    driver: Driver,
+    draw: DrawRule,
+    strength: Strength,
+) {
+    let parsed = match args {
+        [millis, a, b] => millis
+            .parse()
+            .map_err(|_| format!("invalid time: {}", millis))
+            .and_then(|millis| Ok((millis, a.parse()?, b.parse()?))),
+        _ => Err("usage: onitama match <ms per move> <config> <config>".to_string()),
+    };
+    let (millis, a, b): (u64, Search, Search) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{}", err);
+            return;
+        }
+    };
+    let player = |search, strength| Player {
+        evaluator,
+        driver,
+        search,
+        draw,
+        strength,
+    };
+    let players = [player(a, Strength::FULL), player(b, strength)];
+    let mut rng = Generator::default();
+    let mut engines = [players[0].engine(rng.seed()), players[1].engine(rng.seed())];
+
+    let mut score = 0.0;
+    let mut depths = [0; 2];
+    let mut moves = 0;
+    for text in SUITE.iter() {
+        let game: Game = text.parse().unwrap();
+        let tablebase: Rc<TableBase> = TableBase::new(game.all_cards()).into();
+        for &first in &[0, 1] {
+            let (result, game_depths) = play(&tablebase, game, &mut engines, draw, millis);
+            let result = if first == 0 { result } else { 1.0 - result };
+            score += result;
+            depths[first] += game_depths[0];
+            depths[1 - first] += game_depths[1];
+            moves += 1;
+            println!("{} ({} first): {}", text, [a, b][first], result);
+            engines.swap(0, 1);
+        }
+    }
+    println!("{} against {} ({}): {} / {}", a, b, strength, score, moves);
+    println!("total depth: {} against {}", depths[0], depths[1]);
+}
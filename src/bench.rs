@@ -0,0 +1,72 @@
+use std::rc::Rc;
+
+use onitama_move_gen::{gen::Game, tablebase::TableBase};
+
+use crate::{
+    engine::{AlphaBeta, Engine, Limits},
+    node::{Agent, Driver, Evaluator},
+};
+
+// starting positions and positions a few random moves later
+pub const SUITE: [&str; 8] = [
+    "xxXxx/...../...../...../ooOoo ox boar horse elephant crab",
+    "x..xx/.x.X./...../.o.o./ooO.. boar crab ox horse elephant",
+    "xxXxx/...../...../...../ooOoo tiger monkey crane dragon mantis",
+    "xx.xx/..X../....o/o..../o.Oo. tiger dragon crane mantis monkey",
+    "xxXxx/...../...../...../ooOoo frog rabbit goose rooster eel",
+    "x.Xx./..x../...x./O..../oo..o frog goose rabbit eel rooster",
+    "xxXxx/...../...../...../ooOoo elephant cobra monkey mantis goose",
+    "x.xxx/...X./...../o..O./oo..o monkey goose elephant mantis cobra",
+];
+
+const CONFIGS: [(&str, Driver, bool); 3] = [
+    ("mtd without ordering", Driver::Mtd, false),
+    ("mtd", Driver::Mtd, true),
+    ("pvs", Driver::Pvs, true),
+];
+
+// usage: onitama bench <depth>
+// searches every position of the suite to a fixed depth with every search
+// configuration and prints the expanded nodes and the time to reach each depth
+pub fn run(args: &[String], evaluator: Evaluator) {
+    let depth: u8 = match args.first().map(|arg| arg.parse()) {
+        Some(Ok(depth)) => depth,
+        _ => {
+            eprintln!("usage: onitama bench <depth>");
+            return;
+        }
+    };
+
+    let mut totals = [(0, 0); CONFIGS.len()];
+    for text in SUITE.iter() {
+        let game: Game = text.parse().unwrap();
+        let tablebase: Rc<TableBase> = TableBase::new(game.all_cards()).into();
+        println!("{}", text);
+        for (&(name, driver, ordering), total) in CONFIGS.iter().zip(totals.iter_mut()) {
+            let mut engine = AlphaBeta::new(move |tablebase| {
+                Agent::<Game>::new(tablebase)
+                    .with_evaluator(evaluator)
+                    .with_driver(driver)
+                    .with_ordering(ordering)
+            });
+            engine.new_game(tablebase.clone());
+            engine.set_position(&[game]);
+            let limits = Limits {
+                depth,
+                ..Limits::default()
+            };
+            let mut times = vec![];
+            let result = engine.go(limits, &mut |info| times.push(info.millis));
+            let score = result.score.expect("out of memory");
+            println!(
+                "  {}: {} nodes, ms to depth {:?}, score {}",
+                name, result.nodes, times, score
+            );
+            total.0 += result.nodes;
+            total.1 += result.millis;
+        }
+    }
+    for ((name, _, _), (nodes, millis)) in CONFIGS.iter().zip(totals.iter()) {
+        println!("{}: {} nodes, {}ms", name, nodes, millis);
+    }
+}
@@ -0,0 +1,220 @@
+use std::{collections::HashMap, fmt::Display, fs, rc::Rc, str::FromStr, time::Instant};
+
+use onitama_move_gen::{gen::Game, random::Generator, score::Score, tablebase::TableBase};
+
+use crate::node::{Agent, Evaluator, Node};
+
+// a position hash that does not change between builds, unlike `Hash`
+pub fn position_hash(game: Game) -> u64 {
+    fn mix(mut z: u64) -> u64 {
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    mix(mix(game.my as u64 | (game.other as u64) << 32)
+        ^ (game.cards as u64 | (game.table as u64) << 32))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    // the index of the move in `game.forward()`
+    pub index: u8,
+    pub depth: u8,
+    pub score: Score,
+}
+
+// the best move of early positions, written with one "hash index depth score"
+// line per position, the hash in hex, `#` starts a comment
+#[derive(Debug, Default, PartialEq)]
+pub struct Book {
+    entries: HashMap<u64, Entry>,
+}
+
+impl Book {
+    pub fn probe(&self, game: Game) -> Option<Entry> {
+        self.entries.get(&position_hash(game)).copied()
+    }
+
+    // keeps the deeper of the two entries
+    pub fn insert(&mut self, game: Game, entry: Entry) {
+        let old = self.entries.entry(position_hash(game)).or_insert(entry);
+        if old.depth < entry.depth {
+            *old = entry;
+        }
+    }
+
+    pub fn positions(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+impl Display for Book {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|&(&hash, _)| hash);
+        for (hash, entry) in entries {
+            writeln!(
+                f,
+                "{:016x} {} {} {}",
+                hash, entry.index, entry.depth, entry.score.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Book {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut entries = HashMap::new();
+        for line in s.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let parsed = match parts[..] {
+                [hash, index, depth, score] => (|| {
+                    let entry = Entry {
+                        index: index.parse().ok()?,
+                        depth: depth.parse().ok()?,
+                        score: Score(score.parse().ok()?),
+                    };
+                    Some((u64::from_str_radix(hash, 16).ok()?, entry))
+                })(),
+                _ => None,
+            };
+            match parsed {
+                Some((hash, entry)) => entries.insert(hash, entry),
+                None => return Err(format!("invalid book line: {}", line)),
+            };
+        }
+        Ok(Book { entries })
+    }
+}
+
+// the index of the first child of a searched node in `game.forward()`
+fn best_index(game: Game, node: &mut Node) -> u8 {
+    let first = &node.get_nodes()[0];
+    (0..game.count_moves() as u8)
+        .find(|&i| first.is_child(i))
+        .unwrap()
+}
+
+// adds the positions within `plies` of `game` for one player, that player
+// only plays the book move and the other player plays every move
+fn build(
+    book: &mut Book,
+    tablebase: &Rc<TableBase>,
+    game: Game,
+    plies: usize,
+    depth: u8,
+    evaluator: Evaluator,
+    book_to_move: bool,
+) {
+    if plies == 0 || game.is_loss() || tablebase.probe(game).is_some() {
+        return;
+    }
+    if !book_to_move {
+        for new_game in game.forward() {
+            build(book, tablebase, new_game, plies - 1, depth, evaluator, true);
+        }
+        return;
+    }
+    let entry = match book.probe(game) {
+        Some(entry) if entry.depth >= depth => entry,
+        _ => {
+            let agent: Agent = Agent::new(tablebase.clone()).with_evaluator(evaluator);
+            let mut node = agent.new_node(game, 0);
+            for _ in 0..depth {
+                if agent.bns(&mut node).is_none() || node.get_lower().is_mate() {
+                    break;
+                }
+            }
+            let entry = Entry {
+                index: best_index(game, &mut node),
+                depth: node.get_depth(),
+                score: node.get_lower(),
+            };
+            book.insert(game, entry);
+            entry
+        }
+    };
+    let new_game = game.forward().nth(entry.index as usize).unwrap();
+    build(
+        book,
+        tablebase,
+        new_game,
+        plies - 1,
+        depth,
+        evaluator,
+        false,
+    );
+}
+
+// usage: onitama book <book file> <deals> <plies> <depth>
+// adds random deals to the book file, it is created when it does not exist
+pub fn run(args: &[String], evaluator: Evaluator) {
+    if args.len() != 4 {
+        eprintln!("usage: onitama book <book file> <deals> <plies> <depth>");
+        return;
+    }
+    let deals: usize = args[1].parse().expect("invalid number of deals");
+    let plies: usize = args[2].parse().expect("invalid number of plies");
+    let depth: u8 = args[3].parse().expect("invalid depth");
+    let mut book: Book = match fs::read_to_string(&args[0]) {
+        Ok(text) => text.parse().expect("invalid book"),
+        Err(_) => Book::default(),
+    };
+
+    let now = Instant::now();
+    let mut rng = Generator::default();
+    for deal in 0..deals {
+        let cards = rng.deal();
+        let tablebase: Rc<TableBase> = TableBase::new(cards).into();
+        for &book_to_move in &[true, false] {
+            let game = Game::initial(cards);
+            build(
+                &mut book,
+                &tablebase,
+                game,
+                plies,
+                depth,
+                evaluator,
+                book_to_move,
+            );
+        }
+        fs::write(&args[0], book.to_string()).expect("could not write the book");
+        println!(
+            "deal {}: {} positions, {}s",
+            deal,
+            book.positions(),
+            now.elapsed().as_secs()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn book_roundtrip() {
+        let game = Game::initial([0, 1, 2, 3, 4]);
+        let mut book = Book::default();
+        for (i, new_game) in game.forward().enumerate() {
+            let entry = Entry {
+                index: i as u8,
+                depth: 3,
+                score: Score(-(i as i16)),
+            };
+            book.insert(new_game, entry);
+            book.insert(new_game, Entry { depth: 2, ..entry });
+        }
+        assert_eq!(book.positions(), game.count_moves());
+        let text = format!("# test book\n{}", book);
+        assert_eq!(text.parse(), Ok(book));
+        assert!("12 3".parse::<Book>().is_err());
+    }
+}
@@ -24,7 +24,10 @@ pub fn get_next_state(state: &mut StateObj, ws: &mut WebSocket<AutoStream>) -> O
                     break Some(());
                 }
             }
-            LitamaMsg::State(StateMsg::Ended) => break None,
+            LitamaMsg::State(StateMsg::Ended(ended)) => {
+                println!("game ended, winner: {}", ended.winner);
+                break None;
+            }
             LitamaMsg::Error(_) => {}
             msg => panic!(format!("expected state/move message: {:?}", msg)),
         }
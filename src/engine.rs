@@ -0,0 +1,389 @@
+use std::{
+    fmt::Display,
+    rc::Rc,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use onitama_move_gen::{
+    backend::MoveGen, gen::Game, random::Generator, score::Score, tablebase::TableBase,
+};
+
+use crate::{
+    mcts::Mcts,
+    node::{Agent, DrawRule, Driver, Evaluator, SearchConfig, Strength},
+};
+
+// when `go` has to return, zero is no limit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub millis: u64,
+    pub depth: u8,
+    // the time only starts at `ponderhit`
+    pub ponder: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchResult {
+    // the index of the move in `game.forward()`
+    pub index: usize,
+    // the expected line starting with `index`, every move is an index in
+    // `forward()` of the position before it
+    pub pv: Vec<usize>,
+    pub score: Option<Score>,
+    pub depth: u8,
+    pub nodes: usize,
+    pub millis: u64,
+}
+
+// flags that can be set from another thread while `go` runs
+#[derive(Clone, Debug, Default)]
+pub struct Signals {
+    stop: Arc<AtomicBool>,
+    ponderhit: Arc<AtomicBool>,
+}
+
+impl Signals {
+    // the search returns as soon as possible, with the result of the last
+    // iteration that was finished
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    // the opponent played the move that was pondered on, the time limit starts now
+    pub fn ponderhit(&self) {
+        self.ponderhit.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    pub fn is_ponderhit(&self) -> bool {
+        self.ponderhit.load(Ordering::Relaxed)
+    }
+
+    // clears both flags, this is done when a `go` is received and not when the
+    // search starts, so that a `stop` that comes in between is not lost
+    pub fn reset(&self) {
+        self.stop.store(false, Ordering::Relaxed);
+        self.ponderhit.store(false, Ordering::Relaxed);
+    }
+}
+
+// keeps track of the limits of one `go`
+pub struct Clock {
+    start: Instant,
+    limits: Limits,
+    pondering: bool,
+}
+
+impl Clock {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            start: Instant::now(),
+            limits,
+            pondering: limits.ponder,
+        }
+    }
+
+    pub fn elapsed(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    // whether the search should return after reaching `depth`
+    pub fn is_done(&mut self, signals: &Signals, depth: u8) -> bool {
+        if signals.is_stopped() {
+            return true;
+        }
+        if self.pondering {
+            if !signals.is_ponderhit() {
+                return false;
+            }
+            self.pondering = false;
+            self.start = Instant::now();
+        }
+        self.limits.depth > 0 && depth >= self.limits.depth
+            || self.limits.millis > 0 && self.elapsed() >= self.limits.millis
+    }
+}
+
+// alpha-beta with a search config or "mcts"
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Search {
+    AlphaBeta(SearchConfig),
+    Mcts,
+}
+
+impl Display for Search {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Search::AlphaBeta(config) => config.fmt(f),
+            Search::Mcts => f.write_str("mcts"),
+        }
+    }
+}
+
+impl FromStr for Search {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mcts" => Ok(Search::Mcts),
+            _ => Ok(Search::AlphaBeta(s.parse()?)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Player {
+    pub evaluator: Evaluator,
+    pub driver: Driver,
+    pub search: Search,
+    pub draw: DrawRule,
+    // only used by alpha-beta
+    pub strength: Strength,
+}
+
+impl Player {
+    pub fn engine(&self, seed: u64) -> Box<dyn Engine> {
+        match self.search {
+            Search::AlphaBeta(config) => self.alpha_beta(config),
+            Search::Mcts => Box::new(Mcts::new(self.evaluator, seed)),
+        }
+    }
+
+    fn alpha_beta(&self, config: SearchConfig) -> Box<dyn Engine> {
+        let player = *self;
+        Box::new(AlphaBeta::new(move |tablebase| {
+            Agent::<Game>::new(tablebase)
+                .with_evaluator(player.evaluator)
+                .with_driver(player.driver)
+                .with_config(config)
+                .with_draw_rule(player.draw)
+                .with_strength(player.strength)
+        }))
+    }
+}
+
+// a search algorithm that plays one side of a game
+pub trait Engine {
+    // called before the first position of every game, with its tablebase
+    fn new_game(&mut self, tablebase: Rc<TableBase>);
+    // every position since the start of the game, the last one is searched
+    fn set_position(&mut self, history: &[Game]);
+    // searches until the limits are reached or it is stopped, `info` is called
+    // whenever there is a new best move
+    fn go(&mut self, limits: Limits, info: &mut dyn FnMut(&SearchResult)) -> SearchResult;
+    // a clone of these can be used from another thread while `go` runs
+    fn signals(&self) -> &Signals;
+
+    // the search returns as soon as possible
+    fn stop(&self) {
+        self.signals().stop();
+    }
+
+    // the opponent played the move that was pondered on, the time limit starts now
+    fn ponderhit(&self) {
+        self.signals().ponderhit();
+    }
+}
+
+impl<E: Engine + ?Sized> Engine for Box<E> {
+    fn new_game(&mut self, tablebase: Rc<TableBase>) {
+        (**self).new_game(tablebase)
+    }
+
+    fn set_position(&mut self, history: &[Game]) {
+        (**self).set_position(history)
+    }
+
+    fn go(&mut self, limits: Limits, info: &mut dyn FnMut(&SearchResult)) -> SearchResult {
+        (**self).go(limits, info)
+    }
+
+    fn signals(&self) -> &Signals {
+        (**self).signals()
+    }
+}
+
+// `Agent` for the last position that was set, the tree is built by `go`
+pub struct AlphaBeta<M = Game> {
+    make: Box<dyn Fn(Rc<TableBase>) -> Agent<M>>,
+    tablebase: Option<Rc<TableBase>>,
+    agent: Option<Agent<M>>,
+    history: Vec<Game>,
+    rng: Generator,
+    signals: Signals,
+}
+
+impl<M: MoveGen> AlphaBeta<M> {
+    // `make` builds a new agent, a new one is used for every position
+    pub fn new(make: impl Fn(Rc<TableBase>) -> Agent<M> + 'static) -> Self {
+        Self {
+            make: Box::new(make),
+            tablebase: None,
+            agent: None,
+            history: vec![],
+            rng: Generator::default(),
+            signals: Signals::default(),
+        }
+    }
+}
+
+impl<M: MoveGen> Engine for AlphaBeta<M> {
+    fn new_game(&mut self, tablebase: Rc<TableBase>) {
+        self.tablebase = Some(tablebase);
+        self.agent = None;
+        self.history.clear();
+    }
+
+    fn set_position(&mut self, history: &[Game]) {
+        let tablebase = self.tablebase.clone().expect("no game was started");
+        let agent = (self.make)(tablebase).with_stop(self.signals.stop.clone());
+        self.agent = Some(agent);
+        self.history = history.to_vec();
+    }
+
+    fn go(&mut self, limits: Limits, info: &mut dyn FnMut(&SearchResult)) -> SearchResult {
+        let mut clock = Clock::new(limits);
+        let game = *self.history.last().expect("no position was set");
+        let agent = self.agent.as_ref().unwrap();
+        let strength = agent.strength();
+        let mut result = SearchResult::default();
+        // weaker agents do not get the perfect moves of the tablebase
+        if strength == Strength::FULL {
+            if let Some((index, new_game)) = agent.tablebase().best_move(game) {
+                let eval = agent.tablebase().probe(new_game).unwrap().backward();
+                result.index = index;
+                result.pv = vec![index];
+                result.score = Some(Score::from(eval));
+                return result;
+            }
+        }
+
+        // the move to play when the search is stopped before the first iteration
+        result.index = first_move(game);
+        result.pv = vec![result.index];
+        let node = &mut agent.new_node(game, 0);
+        agent.set_history(&self.history);
+        let expanded = agent.expanded();
+        loop {
+            if agent.search(node).is_none() {
+                break;
+            }
+            result.pv = node.pv();
+            result.index = result.pv[0];
+            result.score = Some(node.get_lower());
+            result.depth = node.get_depth();
+            result.nodes = agent.expanded() - expanded;
+            result.millis = clock.elapsed();
+            info(&result);
+            if clock.is_done(&self.signals, result.depth)
+                || node.get_lower().is_mate()
+                || result.depth == strength.max_depth
+                || strength.millis > 0 && result.millis >= strength.millis as u64
+            {
+                break;
+            }
+        }
+        // the search was stopped or ran out of memory before the first iteration
+        if result.depth == 0 {
+            return result;
+        }
+        // a stopped iteration can leave another child first, so only weaker
+        // agents that were not stopped pick the move again
+        if strength != Strength::FULL && !self.signals.is_stopped() {
+            let index = agent.pick(game, node, &mut self.rng);
+            if index != result.index {
+                result.index = index;
+                result.pv = vec![index];
+            }
+        }
+        result
+    }
+
+    fn signals(&self) -> &Signals {
+        &self.signals
+    }
+}
+
+// a move that wins in one if there is one, or else one that does not lose in one
+fn first_move(game: Game) -> usize {
+    if game.is_win() {
+        return game
+            .forward()
+            .position(|new_game| new_game.is_loss())
+            .unwrap();
+    }
+    let evasions = game.evasions();
+    game.forward()
+        .position(|new_game| evasions.contains(&new_game))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "v2")]
+    #[test]
+    fn backends() {
+        use onitama_move_gen_2::{side::Left, state::State};
+
+        fn search<M: MoveGen + 'static>(tablebase: Rc<TableBase>) -> SearchResult {
+            let mut engine = AlphaBeta::new(|tablebase| {
+                Agent::<M>::new(tablebase).with_config(SearchConfig::NONE)
+            });
+            engine.new_game(tablebase);
+            engine.set_position(&[Game::initial([0, 1, 2, 3, 4])]);
+            let limits = Limits {
+                depth: 5,
+                ..Limits::default()
+            };
+            engine.go(limits, &mut |_| {})
+        }
+
+        // the same search finds the same score with either move generator
+        let tablebase: Rc<TableBase> = TableBase::empty().into();
+        let v1 = search::<Game>(tablebase.clone());
+        let v2 = search::<State<Left>>(tablebase);
+        assert_eq!(v1.depth, 5);
+        assert_eq!(v1.score, v2.score);
+        assert_eq!(v1.pv, v2.pv);
+    }
+
+    #[test]
+    fn stopped() {
+        // a position where the first move loses in one and another does not
+        let mut generator = Generator::new(7);
+        let game = (0..)
+            .map(|i| generator.playout(i % 30))
+            .find(|game| {
+                !game.is_loss()
+                    && !game.is_win()
+                    && !TableBase::contains(*game)
+                    && game.forward().next().unwrap().is_win()
+                    && game.forward().any(|new_game| !new_game.is_win())
+            })
+            .unwrap();
+        let player = Player {
+            evaluator: Evaluator::default(),
+            driver: Driver::default(),
+            search: Search::AlphaBeta(SearchConfig::default()),
+            draw: DrawRule::NONE,
+            strength: Strength::FULL,
+        };
+        let mut engine = player.engine(0);
+        engine.new_game(TableBase::empty().into());
+        engine.set_position(&[game]);
+        engine.stop();
+        let result = engine.go(Limits::default(), &mut |_| {});
+        assert_eq!(result.score, None);
+        assert!(!game.forward().nth(result.index).unwrap().is_win());
+    }
+}
@@ -1,18 +1,34 @@
-use std::{env, mem::take, rc::Rc, time::Instant};
+use std::{env, fs, rc::Rc, time::Instant};
 
 use connection::{get_msg, get_next_state};
 use messages::StateObj;
-use onitama_move_gen::{gen::Game, tablebase::TableBase};
+use onitama_move_gen::{
+    card::{patterns, Card},
+    gen::Game,
+    heuristic::Weights,
+    random::Generator,
+    tablebase::TableBase,
+};
 use tungstenite::{client::AutoStream, connect, WebSocket};
 
 use crate::{
+    book::Book,
+    engine::{Engine, Limits, Player, Search},
     messages::{move_to_command, LitamaMsg, StateMsg},
-    node::{Agent, Node},
+    node::{DrawRule, Driver, Evaluator, SearchConfig, Strength},
 };
 
+mod arena;
+mod bench;
+mod book;
 mod connection;
+mod engine;
+mod mcts;
 mod messages;
 pub mod node;
+mod play;
+mod tune;
+mod uci;
 
 extern crate onitama_move_gen;
 #[macro_use]
@@ -20,13 +36,108 @@ extern crate serde_derive;
 extern crate tungstenite;
 
 fn main() {
-    run_loop();
+    let mut args: Vec<String> = env::args().collect();
+    // --weights=<file> loads a parameter file written by `tune` and implies --heuristic
+    let weights = match args.iter().find_map(|arg| arg.strip_prefix("--weights=")) {
+        Some(path) => Some(
+            fs::read_to_string(path)
+                .expect("could not read the weights")
+                .parse()
+                .expect("invalid weights"),
+        ),
+        None if args.iter().any(|arg| arg == "--heuristic") => Some(Weights::default()),
+        None => None,
+    };
+    // --mcts plays with UCT instead of alpha-beta
+    let search = if args.iter().any(|arg| arg == "--mcts") {
+        Search::Mcts
+    } else {
+        Search::AlphaBeta(SearchConfig::default())
+    };
+    let driver = if args.iter().any(|arg| arg == "--pvs") {
+        Driver::Pvs
+    } else {
+        Driver::Mtd
+    };
+    // --draw=<rule> like "repeat=3,plies=200" or "none", threefold by default and
+    // none against litama
+    let draw: Option<DrawRule> = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--draw="))
+        .map(|rule| rule.parse().expect("invalid draw rule"));
+    // litama never ends a game on a repetition
+    let litama_draw = draw.unwrap_or(DrawRule::NONE);
+    let draw = draw.unwrap_or_default();
+    // --book=<file> plays the moves of a book written by `book` when it has them
+    let book = match args.iter().find_map(|arg| arg.strip_prefix("--book=")) {
+        Some(path) => fs::read_to_string(path)
+            .expect("could not read the book")
+            .parse()
+            .expect("invalid book"),
+        None => Book::default(),
+    };
+    // --level=<strength> like "3" or "depth=3,random=40,blunder=5", full strength by default
+    let strength = match args.iter().find_map(|arg| arg.strip_prefix("--level=")) {
+        Some(strength) => strength.parse().expect("invalid level"),
+        None => Strength::FULL,
+    };
+    args.retain(|arg| !arg.starts_with("--"));
+
+    let evaluator = weights.map_or(Evaluator::Table, Evaluator::Heuristic);
+    let player = Player {
+        evaluator,
+        driver,
+        search,
+        draw,
+        strength,
+    };
+    match args.get(1).map(String::as_str) {
+        Some("tune") => tune::run(&args[2..], weights.unwrap_or_default()),
+        Some("bench") => bench::run(&args[2..], evaluator),
+        Some("book") => book::run(&args[2..], evaluator),
+        Some("match") => arena::run(&args[2..], evaluator, driver, draw, strength),
+        Some("uci") => uci::run(player),
+        Some("play") => play::run(&args[2..], player),
+        Some("cards") => print_cards(&args[2..]),
+        _ => {
+            // weaker players do not get the perfect moves of the book
+            let book = if strength == Strength::FULL {
+                book
+            } else {
+                Book::default()
+            };
+            let player = Player {
+                draw: litama_draw,
+                ..player
+            };
+            run_loop(args, player.engine(Generator::default().seed()), book);
+        }
+    }
+}
+
+// usage: onitama cards [card...]
+// prints how the named cards move, or all of them
+fn print_cards(names: &[String]) {
+    let cards: Result<Vec<Card>, _> = if names.is_empty() {
+        Ok(Card::all().collect())
+    } else {
+        names.iter().map(|name| name.parse()).collect()
+    };
+    match cards {
+        Ok(cards) => {
+            for card in cards {
+                println!("{}, {} stamp\n{}", card, card.color(), card.pattern(false));
+            }
+        }
+        Err(err) => eprintln!("{}", err),
+    }
 }
 
-fn run_loop() -> Option<()> {
+// litama only ends a game when it is won, so by default the search does not
+// score repetitions as ties either
+fn run_loop(args: Vec<String>, mut engine: impl Engine, book: Book) -> Option<()> {
     let mut ws = connect("ws://litama.herokuapp.com").unwrap().0;
 
-    let args: Vec<String> = env::args().collect();
     let (index, token, match_id) = if args.len() > 1 {
         ws.write_message(format!("join {} Omega", args[1]).into())
             .unwrap();
@@ -70,78 +181,81 @@ fn run_loop() -> Option<()> {
     let now1 = Instant::now();
     let tablebase: Rc<TableBase> = TableBase::new(state.all_cards()).into();
     println!("tablebase took: {}", now1.elapsed().as_secs_f32());
+    let cards: Vec<_> = state
+        .all_cards()
+        .iter()
+        .map(|&id| (Card::new(id).unwrap(), false))
+        .collect();
+    println!("cards:\n{}", patterns(&cards));
 
+    let mut history = vec![state.game()];
     if state.index() != index {
         get_next_state(&mut state, &mut ws)?;
+        history.push(state.game());
     }
 
-    let mut agent = Agent::new(tablebase.clone());
-    let mut node = agent.new_node(state.game(), 0);
-
+    engine.new_game(tablebase);
     let mut runner = Runner {
         ws,
         state,
         token,
         match_id,
+        history,
+        book,
+        engine,
     };
-
     loop {
-        let other_agent = Agent::new(tablebase.clone());
-
-        runner.run(&agent, &mut node)?;
-
-        let mut other_node = other_agent.copy(&node);
-        agent = Agent::new(tablebase.clone());
-
-        runner.run(&other_agent, &mut other_node)?;
-
-        node = agent.copy(&other_node);
+        runner.run()?;
     }
 }
 
-struct Runner {
+struct Runner<E> {
     ws: WebSocket<AutoStream>,
     state: StateObj,
     token: String,
     match_id: String,
+    // every position of the game so far, the last one is `state.game()`
+    history: Vec<Game>,
+    book: Book,
+    engine: E,
 }
 
-impl Runner {
-    fn run<'a>(&mut self, agent: &'a Agent, node: &mut Node<'a>) -> Option<()> {
-        let now2 = Instant::now();
-        loop {
-            let res = agent.bns(node);
-            println!("* {}", node.get_lower());
-            if now2.elapsed().as_millis() > 1000
-                || node.get_lower() == 127
-                || node.get_lower() == -127
-                || res.is_none()
-            {
-                break;
-            }
-        }
-        *node = take(node.get_nodes().iter_mut().next().unwrap());
-
+impl<E: Engine> Runner<E> {
+    fn run(&mut self) -> Option<()> {
         let game = self.state.game();
-        let cond = |(i, _): &(usize, Game)| node.is_child(*i as u8);
-        let (_, new_game) = game.forward().enumerate().find(cond).unwrap();
+        let book = self.book.probe(game).and_then(|entry| {
+            let i = entry.index as usize;
+            game.forward().nth(i).map(|new_game| (i, new_game))
+        });
+        let new_game = match book {
+            Some((_, new_game)) => {
+                println!("book move");
+                new_game
+            }
+            None => {
+                self.engine.set_position(&self.history);
+                let limits = Limits {
+                    millis: 1000,
+                    ..Limits::default()
+                };
+                let result = self.engine.go(limits, &mut |info| {
+                    println!("depth: {}", info.depth);
+                    if let Some(score) = info.score {
+                        println!("* {}", score);
+                    }
+                });
+                game.forward().nth(result.index).unwrap()
+            }
+        };
 
         let flip = self.state.current_turn == "red";
         let command = move_to_command(game, new_game, &self.match_id, &self.token, flip);
         self.ws.write_message(command.into()).unwrap();
 
         get_next_state(&mut self.state, &mut self.ws)?;
+        self.history.push(self.state.game());
         get_next_state(&mut self.state, &mut self.ws)?;
-
-        if agent.expand(node).is_some() {
-            let cond = |(_, g): &(usize, Game)| g == &self.state.game();
-            let (i, _) = new_game.forward().enumerate().find(cond).unwrap();
-            let cond = |n: &&mut Node| n.is_child(i as u8);
-            *node = take(node.get_nodes().iter_mut().find(cond).unwrap())
-        } else {
-            *node = agent.new_node(self.state.game(), 0)
-        }
-
+        self.history.push(self.state.game());
         Some(())
     }
 }
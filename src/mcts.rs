@@ -0,0 +1,279 @@
+use std::rc::Rc;
+
+use onitama_move_gen::{
+    gen::Game,
+    heuristic::Weights,
+    random::Generator,
+    score::{Score, HEURISTIC},
+    tablebase::TableBase,
+    tune::sigmoid,
+};
+
+use crate::{
+    engine::{Clock, Engine, Limits, SearchResult, Signals},
+    node::Evaluator,
+};
+
+// exploration constant of UCT
+const EXPLORATION: f64 = 1.4;
+// playouts are cut off after this many plies
+const PLAYOUT_PLIES: usize = 40;
+// maps heuristic values of cut off playouts to expected scores
+const PLAYOUT_SCALE: f64 = 0.05;
+
+struct MctsNode {
+    game: Game,
+    // children are stored next to each other, `children == 0` is not expanded
+    first: u32,
+    children: u8,
+    visits: u32,
+    // the sum of the results for the player that moved into this node
+    total: f64,
+    // the result for the player to move when it is known
+    known: Option<f64>,
+}
+
+impl MctsNode {
+    fn new(tablebase: &TableBase, game: Game) -> Self {
+        Self {
+            game,
+            first: 0,
+            children: 0,
+            visits: 0,
+            total: 0.0,
+            known: known(tablebase, game),
+        }
+    }
+}
+
+// the exact result for the player to move, from the rules or the tablebase
+fn known(tablebase: &TableBase, game: Game) -> Option<f64> {
+    if game.is_loss() {
+        return Some(0.0);
+    }
+    if game.is_win() {
+        return Some(1.0);
+    }
+    match tablebase.eval(game) {
+        (true, score) => Some(expected(score)),
+        _ => None,
+    }
+}
+
+fn expected(score: Score) -> f64 {
+    if score.is_win() {
+        1.0
+    } else if score.is_loss() {
+        0.0
+    } else {
+        0.5
+    }
+}
+
+// UCT with the tablebase as an oracle, the evaluator picks the playouts:
+// random moves for `Table` and moves that are best for the heuristic for
+// `Heuristic`, cut off playouts are scored by the heuristic
+pub struct Mcts {
+    tablebase: Option<Rc<TableBase>>,
+    evaluator: Evaluator,
+    nodes: Vec<MctsNode>,
+    game: Game,
+    rng: Generator,
+    signals: Signals,
+}
+
+impl Mcts {
+    pub fn new(evaluator: Evaluator, seed: u64) -> Self {
+        Self {
+            tablebase: None,
+            evaluator,
+            nodes: vec![],
+            game: Game::default(),
+            rng: Generator::new(seed),
+            signals: Signals::default(),
+        }
+    }
+
+    // one selection, expansion, playout and backup, returns the depth of the line
+    fn iterate(&mut self) -> u8 {
+        let mut path = vec![0];
+        let mut index = 0;
+        // the result for the player to move at `index`
+        let result = loop {
+            let node = &self.nodes[index];
+            // the root is searched even when it is known, to find the move
+            if let (Some(result), false) = (node.known, index == 0) {
+                break result;
+            }
+            if node.children == 0 {
+                if node.visits == 0 {
+                    break self.playout(node.game);
+                }
+                self.expand(index);
+            }
+            index = self.select(index);
+            path.push(index);
+        };
+        let mut result = 1.0 - result;
+        for &index in path.iter().rev() {
+            let node = &mut self.nodes[index];
+            node.visits += 1;
+            node.total += result;
+            result = 1.0 - result;
+        }
+        (path.len() - 1).min(u8::MAX as usize) as u8
+    }
+
+    fn expand(&mut self, index: usize) {
+        let game = self.nodes[index].game;
+        let first = self.nodes.len();
+        let tablebase = self.tablebase.as_deref().unwrap();
+        for new_game in game.forward() {
+            let new_node = MctsNode::new(tablebase, new_game);
+            self.nodes.push(new_node);
+        }
+        let node = &mut self.nodes[index];
+        node.first = first as u32;
+        node.children = game.count_moves() as u8;
+    }
+
+    fn result(&self, depth: u8, clock: &Clock) -> SearchResult {
+        // the most visited child at every level
+        let mut pv = vec![];
+        let mut node = &self.nodes[0];
+        while node.children > 0 {
+            let first = node.first as usize;
+            let index = (0..node.children as usize)
+                .max_by_key(|&i| self.nodes[first + i].visits)
+                .unwrap();
+            pv.push(index);
+            node = &self.nodes[first + index];
+            if node.visits == 0 {
+                break;
+            }
+        }
+        let child = &self.nodes[self.nodes[0].first as usize + pv[0]];
+        // the expected result of the best move as a heuristic value, a sure win
+        // is `HEURISTIC` and a sure loss is `-HEURISTIC`, which stay below the
+        // tablebase and mate scores
+        let expected = child.total / child.visits.max(1) as f64;
+        let value = (expected * 2.0 - 1.0) * HEURISTIC as f64;
+        SearchResult {
+            index: pv[0],
+            pv,
+            score: Some(Score::new_heuristic(value as i32)),
+            depth,
+            nodes: self.nodes.len(),
+            millis: clock.elapsed(),
+        }
+    }
+
+    fn select(&self, index: usize) -> usize {
+        let node = &self.nodes[index];
+        let log = (node.visits.max(1) as f64).ln();
+        let first = node.first as usize;
+        (first..first + node.children as usize)
+            .max_by(|&a, &b| {
+                let (a, b) = (self.uct(a, log), self.uct(b, log));
+                a.partial_cmp(&b).unwrap()
+            })
+            .unwrap()
+    }
+
+    fn uct(&self, index: usize, log: f64) -> f64 {
+        let node = &self.nodes[index];
+        // a move that wins for sure is always taken
+        if node.known == Some(0.0) {
+            f64::INFINITY
+        } else if node.visits == 0 {
+            f64::MAX
+        } else {
+            let visits = node.visits as f64;
+            node.total / visits + EXPLORATION * (log / visits).sqrt()
+        }
+    }
+
+    // the result for the player to move in `game`
+    fn playout(&mut self, mut game: Game) -> f64 {
+        let tablebase = self.tablebase.clone().unwrap();
+        // the result is flipped once for every ply
+        let mut flip = false;
+        let result = |result: f64, flip: bool| if flip { 1.0 - result } else { result };
+        for _ in 0..PLAYOUT_PLIES {
+            if let Some(known) = known(&tablebase, game) {
+                return result(known, flip);
+            }
+            game = match self.evaluator {
+                Evaluator::Table => {
+                    let i = self.rng.below(game.count_moves());
+                    game.forward().nth(i).unwrap()
+                }
+                Evaluator::Heuristic(weights) => self.heuristic_move(game, weights),
+            };
+            flip = !flip;
+        }
+        let estimate = match self.evaluator {
+            Evaluator::Table => 0.5,
+            Evaluator::Heuristic(weights) => sigmoid(PLAYOUT_SCALE, weights.evaluate(game) as f64),
+        };
+        result(estimate, flip)
+    }
+
+    // one of the moves that are best for the heuristic, taken at random half of
+    // the time so that playouts differ
+    fn heuristic_move(&mut self, game: Game, weights: Weights) -> Game {
+        if self.rng.below(2) == 0 {
+            let i = self.rng.below(game.count_moves());
+            return game.forward().nth(i).unwrap();
+        }
+        game.forward()
+            .min_by_key(|&new_game| weights.evaluate(new_game))
+            .unwrap()
+    }
+}
+
+impl Engine for Mcts {
+    fn new_game(&mut self, tablebase: Rc<TableBase>) {
+        self.tablebase = Some(tablebase);
+    }
+
+    // the tree is not kept between moves and the history is not used
+    fn set_position(&mut self, history: &[Game]) {
+        self.game = *history.last().unwrap();
+    }
+
+    fn go(&mut self, limits: Limits, info: &mut dyn FnMut(&SearchResult)) -> SearchResult {
+        let mut clock = Clock::new(limits);
+        self.nodes.clear();
+        let tablebase = self.tablebase.clone().expect("no game was started");
+        // the tablebase has the perfect move, like for alpha-beta
+        if let Some((index, new_game)) = tablebase.best_move(self.game) {
+            let eval = tablebase.probe(new_game).unwrap().backward();
+            self.signals.reset();
+            return SearchResult {
+                index,
+                pv: vec![index],
+                score: Some(Score::from(eval)),
+                ..SearchResult::default()
+            };
+        }
+        self.nodes.push(MctsNode::new(&tablebase, self.game));
+        self.expand(0);
+        let mut depth = 0;
+        for iteration in 1.. {
+            depth = depth.max(self.iterate());
+            if iteration % 64 == 0 && clock.is_done(&self.signals, 0) {
+                break;
+            }
+        }
+        let result = self.result(depth, &clock);
+        info(&result);
+        // a stop that came in during this search does not end the next one
+        self.signals.reset();
+        result
+    }
+
+    fn signals(&self) -> &Signals {
+        &self.signals
+    }
+}
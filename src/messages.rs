@@ -43,7 +43,15 @@ pub enum StateMsg {
     #[serde(rename = "in progress")]
     InProgress(StateObj),
     #[serde(rename = "ended")]
-    Ended,
+    Ended(EndedObj),
+}
+
+// litama ends a game only when a player has won
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EndedObj {
+    #[serde(default)]
+    pub winner: String,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
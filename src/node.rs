@@ -1,22 +1,67 @@
 use std::ptr::{self, NonNull};
-use std::{alloc::Layout, cmp::max, mem::swap, rc::Rc, slice, unreachable};
+use std::{
+    alloc::Layout,
+    cell::{Cell, RefCell},
+    cmp::{max, min, Reverse},
+    fmt::Display,
+    marker::PhantomData,
+    mem::swap,
+    rc::Rc,
+    slice,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    unreachable,
+};
 
 use bumpalo::Bump;
-use onitama_move_gen::{gen::Game, tablebase::TableBase};
+use onitama_move_gen::{
+    backend::MoveGen,
+    gen::{Game, My, Other, PIECE_MASK},
+    heuristic::{temple_distance, Weights},
+    random::Generator,
+    score::Score,
+    tablebase::TableBase,
+    SHIFTED,
+};
+
+// one entry for every move key
+const HISTORY_SIZE: usize = 16 * 25 * 4;
+// children are sorted on these, from high to low
+const CAPTURE: u32 = 1 << 28;
+const KILLER: u32 = 1 << 27;
+const KING: u32 = 1 << 26;
+const HISTORY_MAX: u32 = KING - 1;
+// `key` holds the move in the low bits and these flags in the high bits
+const MOVE_MASK: u16 = (1 << 11) - 1;
+// the player to move can lose in one
+const THREAT: u16 = 1 << 15;
+// the move took a piece
+const TAKE: u16 = 1 << 14;
+// the king of the player that moved is at most two steps from the temple
+const TEMPLE_FLAG: u16 = 1 << 13;
+// the most plies a single line is extended for threats
+const MAX_EXTENSIONS: u8 = 8;
+// first aspiration window around the last value
+const WINDOW: i16 = 16;
 
 #[derive(Clone, Copy, Default)]
 pub struct Leaf {
     table: bool,
-    value: i8,
+    value: Score,
     child: u8,
+    key: u16,
     game: Game,
 }
 
 pub struct Branch<'a> {
-    lower: i8,
-    upper: i8,
+    lower: Score,
+    upper: Score,
     depth: u8,
     child: u8,
+    key: u16,
     nodes: &'a mut [Node<'a>],
 }
 
@@ -27,10 +72,47 @@ pub enum Node<'a> {
 
 impl<'a> Node<'a> {
     pub fn is_child(&self, child: u8) -> bool {
-        (match self {
+        self.child() == child
+    }
+    fn child(&self) -> u8 {
+        match self {
             Node::Leaf(leaf) => leaf.child,
             Node::Branch(branch) => branch.child,
-        }) == child
+        }
+    }
+    // the first child at every level, the best line of the last search as
+    // indices in `game.forward()`
+    pub fn pv(&self) -> Vec<usize> {
+        let mut pv = vec![];
+        let mut node = self;
+        while let Node::Branch(Branch { nodes, .. }) = node {
+            match nodes.first() {
+                Some(first) => node = first,
+                None => break,
+            }
+            pv.push(node.child() as usize);
+        }
+        pv
+    }
+    // the move that leads to this node, see `move_key`
+    pub fn key(&self) -> u16 {
+        self.raw_key() & MOVE_MASK
+    }
+    pub fn is_threatened(&self) -> bool {
+        self.raw_key() & THREAT != 0
+    }
+    // not a capture, not a threat and not in the tablebase
+    fn is_quiet(&self) -> bool {
+        match self {
+            Node::Leaf(leaf) if leaf.table => false,
+            _ => self.raw_key() & (THREAT | TAKE | TEMPLE_FLAG) == 0,
+        }
+    }
+    fn raw_key(&self) -> u16 {
+        match self {
+            Node::Leaf(leaf) => leaf.key,
+            Node::Branch(branch) => branch.key,
+        }
     }
     pub fn as_branch(&mut self) -> &mut Branch<'a> {
         match self {
@@ -44,7 +126,7 @@ impl<'a> Node<'a> {
     pub fn get_depth(&mut self) -> u8 {
         self.as_branch().depth
     }
-    pub fn get_lower(&mut self) -> i8 {
+    pub fn get_lower(&mut self) -> Score {
         match self {
             Node::Leaf(leaf) => leaf.value,
             Node::Branch(branch) => branch.lower,
@@ -70,19 +152,403 @@ impl Default for Node<'_> {
     }
 }
 
-pub struct Agent {
+// how leaves outside of the tablebase are valued
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Evaluator {
+    Table,
+    Heuristic(Weights),
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator::Table
+    }
+}
+
+// how `Agent::search` goes one ply deeper
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Driver {
+    // repeated null window searches, see `Agent::bns`
+    Mtd,
+    // principal variation search with aspiration windows, see `Agent::aspiration`
+    Pvs,
+}
+
+impl Default for Driver {
+    fn default() -> Self {
+        Driver::Mtd
+    }
+}
+
+// reductions and extensions, written like "lmr=3/1,king,temple" or "none"
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchConfig {
+    // quiet children after this many are first searched this much shallower
+    pub lmr_after: usize,
+    pub lmr_reduction: u8,
+    // extend when the king is attacked
+    pub king_extension: bool,
+    // extend when the king of the other player is close to the temple
+    pub temple_extension: bool,
+}
+
+impl SearchConfig {
+    pub const NONE: SearchConfig = SearchConfig {
+        lmr_after: 0,
+        lmr_reduction: 0,
+        king_extension: false,
+        temple_extension: false,
+    };
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            lmr_after: 3,
+            lmr_reduction: 1,
+            king_extension: true,
+            temple_extension: true,
+        }
+    }
+}
+
+impl Display for SearchConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![];
+        if self.lmr_reduction > 0 {
+            parts.push(format!("lmr={}/{}", self.lmr_after, self.lmr_reduction));
+        }
+        if self.king_extension {
+            parts.push("king".to_string());
+        }
+        if self.temple_extension {
+            parts.push("temple".to_string());
+        }
+        if parts.is_empty() {
+            parts.push("none".to_string());
+        }
+        f.write_str(&parts.join(","))
+    }
+}
+
+impl FromStr for SearchConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = SearchConfig::NONE;
+        for part in s.split(',') {
+            match part {
+                "none" => {}
+                "king" => config.king_extension = true,
+                "temple" => config.temple_extension = true,
+                _ => {
+                    let lmr = part.strip_prefix("lmr=").and_then(|lmr| {
+                        let (after, reduction) = lmr.split_at(lmr.find('/')?);
+                        Some((after.parse().ok()?, reduction[1..].parse().ok()?))
+                    });
+                    match lmr {
+                        Some((after, reduction)) => {
+                            config.lmr_after = after;
+                            config.lmr_reduction = reduction;
+                        }
+                        None => return Err(format!("invalid search option: {}", part)),
+                    }
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+// when a game is drawn, written like "repeat=3,plies=200" or "none", a zero
+// turns that part off
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DrawRule {
+    // the same position with the same player to move this many times
+    pub repetitions: u8,
+    // this many plies since the start of the game
+    pub max_plies: u16,
+}
+
+impl DrawRule {
+    pub const NONE: DrawRule = DrawRule {
+        repetitions: 0,
+        max_plies: 0,
+    };
+
+    fn is_active(&self) -> bool {
+        *self != DrawRule::NONE
+    }
+
+    // `path` holds every position since the start of the game, the last one is
+    // the current position
+    pub fn is_draw(&self, path: &[Game]) -> bool {
+        let game = match path.last() {
+            Some(game) => game,
+            None => return false,
+        };
+        if self.max_plies > 0 && path.len() > self.max_plies as usize {
+            return true;
+        }
+        self.repetitions > 0
+            && path.iter().rev().step_by(2).filter(|&g| g == game).count()
+                >= self.repetitions as usize
+    }
+}
+
+impl Default for DrawRule {
+    fn default() -> Self {
+        Self {
+            repetitions: 3,
+            max_plies: 0,
+        }
+    }
+}
+
+impl Display for DrawRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![];
+        if self.repetitions > 0 {
+            parts.push(format!("repeat={}", self.repetitions));
+        }
+        if self.max_plies > 0 {
+            parts.push(format!("plies={}", self.max_plies));
+        }
+        if parts.is_empty() {
+            parts.push("none".to_string());
+        }
+        f.write_str(&parts.join(","))
+    }
+}
+
+impl FromStr for DrawRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rule = DrawRule::NONE;
+        for part in s.split(',') {
+            match part {
+                "none" => {}
+                "threefold" => rule.repetitions = 3,
+                _ => {
+                    if let Some(Ok(n)) = part.strip_prefix("repeat=").map(str::parse) {
+                        rule.repetitions = n;
+                    } else if let Some(Ok(n)) = part.strip_prefix("plies=").map(str::parse) {
+                        rule.max_plies = n;
+                    } else {
+                        return Err(format!("invalid draw rule: {}", part));
+                    }
+                }
+            }
+        }
+        Ok(rule)
+    }
+}
+
+// handicaps for weaker play, written like "depth=3,ms=500,random=40,blunder=5",
+// as a level from 1 to 5 or as "full" for no handicap, a zero turns that part off
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Strength {
+    // the deepest search
+    pub max_depth: u8,
+    // the most time for one move
+    pub millis: u32,
+    // children this much below the best are picked at random, better ones more often
+    pub margin: i16,
+    // the chance in percent of a random move
+    pub blunder: u8,
+}
+
+impl Strength {
+    pub const FULL: Strength = Strength {
+        max_depth: 0,
+        millis: 0,
+        margin: 0,
+        blunder: 0,
+    };
+
+    pub fn level(level: u8) -> Option<Self> {
+        let (max_depth, millis, margin, blunder) = match level {
+            1 => (1, 0, 200, 20),
+            2 => (2, 0, 100, 10),
+            3 => (3, 0, 50, 5),
+            4 => (5, 300, 20, 0),
+            5 => return Some(Strength::FULL),
+            _ => return None,
+        };
+        Some(Strength {
+            max_depth,
+            millis,
+            margin,
+            blunder,
+        })
+    }
+}
+
+impl Default for Strength {
+    fn default() -> Self {
+        Strength::FULL
+    }
+}
+
+impl Display for Strength {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![];
+        if self.max_depth > 0 {
+            parts.push(format!("depth={}", self.max_depth));
+        }
+        if self.millis > 0 {
+            parts.push(format!("ms={}", self.millis));
+        }
+        if self.margin > 0 {
+            parts.push(format!("random={}", self.margin));
+        }
+        if self.blunder > 0 {
+            parts.push(format!("blunder={}", self.blunder));
+        }
+        if parts.is_empty() {
+            parts.push("full".to_string());
+        }
+        f.write_str(&parts.join(","))
+    }
+}
+
+impl FromStr for Strength {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(level) = s.parse() {
+            return Strength::level(level).ok_or(format!("unknown level: {}", level));
+        }
+        let mut strength = Strength::FULL;
+        for part in s.split(',') {
+            let (name, value) = match part.find('=') {
+                Some(i) => (&part[..i], &part[i + 1..]),
+                None => (part, ""),
+            };
+            let valid = match name {
+                "full" => true,
+                "depth" => value.parse().map(|v| strength.max_depth = v).is_ok(),
+                "ms" => value.parse().map(|v| strength.millis = v).is_ok(),
+                "random" => value.parse().map(|v| strength.margin = v).is_ok(),
+                "blunder" => value.parse().map(|v| strength.blunder = v).is_ok(),
+                _ => false,
+            };
+            if !valid {
+                return Err(format!("invalid strength: {}", part));
+            }
+        }
+        Ok(strength)
+    }
+}
+
+// the moves are generated by `M`, the tree always holds `Game`
+pub struct Agent<M = Game> {
     tablebase: Rc<TableBase>,
+    evaluator: Evaluator,
+    driver: Driver,
+    config: SearchConfig,
+    strength: Strength,
+    ordering: bool,
+    // killer moves are kept per remaining depth, which is the same as per ply
+    // within one iteration of `bns`
+    killers: Box<[Cell<[u16; 2]>]>,
+    history: Box<[Cell<u32>]>,
+    // extensions on the current line
+    extensions: Cell<u8>,
+    draw: DrawRule,
+    // the positions of the game followed by those of the current line, only
+    // kept when there is a draw rule
+    path: RefCell<Vec<Game>>,
+    // the number of positions in `path` that were played
+    played: Cell<usize>,
+    expanded: Cell<usize>,
+    // searches return `None` when this is set, like when they run out of memory
+    stop: Arc<AtomicBool>,
+    // the moves of the node that is expanded
+    moves: Cell<Vec<(usize, Game)>>,
     bump: Bump,
+    move_gen: PhantomData<M>,
 }
 
-impl Agent {
+impl<M: MoveGen> Agent<M> {
     pub fn new(tablebase: Rc<TableBase>) -> Self {
         Self {
             tablebase,
+            evaluator: Evaluator::default(),
+            driver: Driver::default(),
+            config: SearchConfig::default(),
+            strength: Strength::default(),
+            ordering: true,
+            killers: vec![Cell::new([u16::MAX; 2]); 256].into(),
+            history: vec![Cell::new(0); HISTORY_SIZE].into(),
+            extensions: Cell::new(0),
+            draw: DrawRule::default(),
+            path: RefCell::new(vec![]),
+            played: Cell::new(0),
+            expanded: Cell::new(0),
+            stop: Arc::new(AtomicBool::new(false)),
+            moves: Cell::new(vec![]),
             bump: Bump::new(),
+            move_gen: PhantomData,
         }
     }
 
+    pub fn with_driver(mut self, driver: Driver) -> Self {
+        self.driver = driver;
+        self
+    }
+
+    pub fn with_config(mut self, config: SearchConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_strength(mut self, strength: Strength) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    pub fn strength(&self) -> Strength {
+        self.strength
+    }
+
+    pub fn with_draw_rule(mut self, draw: DrawRule) -> Self {
+        self.draw = draw;
+        self
+    }
+
+    // the positions since the start of the game, the last one is the position
+    // that is searched, draws are only found when this is set
+    pub fn set_history(&self, games: &[Game]) {
+        *self.path.borrow_mut() = games.to_vec();
+        self.played.set(games.len());
+    }
+
+    pub fn with_ordering(mut self, ordering: bool) -> Self {
+        self.ordering = ordering;
+        self
+    }
+
+    pub fn with_stop(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    // number of nodes that were expanded by this agent
+    pub fn expanded(&self) -> usize {
+        self.expanded.get()
+    }
+
+    pub fn with_evaluator(mut self, evaluator: Evaluator) -> Self {
+        self.evaluator = evaluator;
+        self
+    }
+
+    pub fn tablebase(&self) -> &TableBase {
+        &self.tablebase
+    }
+
     pub fn copy<'a>(&'a self, node: &Node) -> Node<'a> {
         match node {
             Node::Leaf(leaf) => Node::Leaf(*leaf),
@@ -94,49 +560,101 @@ impl Agent {
                 upper: branch.upper,
                 depth: branch.depth,
                 child: branch.child,
+                key: branch.key,
             }),
         }
     }
 
     #[inline(always)]
     pub fn new_node(&self, game: Game, child: u8) -> Node {
-        let (table, value) = if game.is_loss() {
-            (true, -127)
+        let threatened = !game.is_loss() && game.is_threatened();
+        let (table, mut value) = if game.is_loss() {
+            (true, Score::new_loss(0))
+        } else if game.is_win() {
+            (true, Score::new_win(1))
+        } else if threatened && M::try_for_each_move(game, true, |_, _| None).is_some() {
+            // there is no evasion
+            (true, Score::new_loss(2))
         } else {
             self.tablebase.eval(game)
         };
+        if let (false, Evaluator::Heuristic(weights)) = (table, self.evaluator) {
+            value = Score::new_heuristic(weights.evaluate(game));
+        }
+        let mut key = if threatened { THREAT } else { 0 };
+        if temple_distance(game.king::<Other>()) <= 2 {
+            key |= TEMPLE_FLAG;
+        }
         Node::Leaf(Leaf {
             game,
             table,
             value,
             child,
+            key,
         })
     }
 
     pub fn expand<'a>(&'a self, node: &mut Node<'a>) -> Option<()> {
+        self.expand_at(node, 0)
+    }
+
+    // the children are sorted with the killers of `depth`, when the player
+    // to move is threatened only the evasions are generated
+    fn expand_at<'a>(&'a self, node: &mut Node<'a>, depth: u8) -> Option<()> {
         if let Node::Leaf(leaf) = node {
-            let mut iter = leaf
-                .game
-                .forward()
-                .enumerate()
-                .map(|(new_child, new_game)| self.new_node(new_game, new_child as u8));
+            if self.stop.load(Ordering::Relaxed) {
+                return None;
+            }
+            let game = leaf.game;
+            let evasions = leaf.key & THREAT != 0 && !leaf.table;
+            let mut moves = self.moves.take();
+            moves.clear();
+            // `M` can not be used after the end of the game and it may leave
+            // out every move
+            if !leaf.table {
+                M::for_each_move(game, evasions, |index, new_game| {
+                    moves.push((index, new_game))
+                });
+            }
+            if moves.is_empty() {
+                Game::for_each_move(game, false, |index, new_game| moves.push((index, new_game)));
+            }
+            // ties are broken the same way for every `M`
+            moves.sort_unstable_by_key(|&(index, _)| index);
+            let len = moves.len();
+            let mut iter = moves.iter().map(|&(new_child, new_game)| {
+                let mut new_node = self.new_node(new_game, new_child as u8);
+                if let Node::Leaf(new_leaf) = &mut new_node {
+                    new_leaf.key |= move_key(game, new_game);
+                    if new_game.count_pieces() < game.swap().count_pieces() {
+                        new_leaf.key |= TAKE;
+                    }
+                }
+                new_node
+            });
 
-            let layout = Layout::array::<Node>(iter.len()).unwrap();
+            let layout = Layout::array::<Node>(len).unwrap();
             let dst = self.bump.try_alloc_layout(layout).ok()?.cast::<Node>();
 
             let nodes = unsafe {
-                for i in 0..iter.len() {
+                for i in 0..len {
                     ptr::write(dst.as_ptr().add(i), iter.next().unwrap());
                 }
-                let result = slice::from_raw_parts_mut(dst.as_ptr(), iter.len());
+                let result = slice::from_raw_parts_mut(dst.as_ptr(), len);
                 debug_assert_eq!(Layout::for_value(result), layout);
                 result
             };
+            self.moves.set(moves);
+            if self.ordering {
+                nodes.sort_by_key(|new_node| Reverse(self.order(game, new_node, depth)));
+            }
+            self.expanded.set(self.expanded.get() + 1);
             *node = Node::Branch(Branch {
                 lower: leaf.value,
-                upper: 127,
+                upper: Score::MAX,
                 depth: 0,
                 child: leaf.child,
+                key: leaf.key,
                 nodes,
             });
         };
@@ -144,71 +662,117 @@ impl Agent {
     }
 
     pub fn bns<'a>(&'a self, node: &mut Node<'a>) -> Option<()> {
+        self.extensions.set(0);
+        self.path.borrow_mut().truncate(self.played.get());
         self.expand(node)?;
         let depth = node.as_branch().depth + 1;
         let mut guess = node.as_branch().lower;
-        println!("depth: {}", depth);
-        while node.as_branch().depth != depth || node.as_branch().lower != node.as_branch().upper {
+        loop {
+            let branch = node.as_branch();
+            if branch.depth == depth && branch.lower == branch.upper {
+                break;
+            }
             let beta = max(node.as_branch().lower.saturating_add(1), guess);
             guess = self.alpha_beta(node, beta, depth)?;
         }
         assert!(node.as_branch().depth == depth);
         Some(())
+    }
+
+    // searches one ply deeper than the last search, like `bns`, with
+    // principal variation search in a window around the last value
+    pub fn aspiration<'a>(&'a self, node: &mut Node<'a>) -> Option<()> {
+        self.extensions.set(0);
+        self.path.borrow_mut().truncate(self.played.get());
+        self.expand(node)?;
+        let depth = node.as_branch().depth + 1;
+        let guess = node.as_branch().lower;
+        let (mut alpha, mut beta) = (
+            max(guess.saturating_add(-WINDOW), Score::MIN),
+            min(guess.saturating_add(WINDOW), Score::MAX),
+        );
+        let mut window = WINDOW;
+        loop {
+            let score = self.pvs(node, alpha, beta, depth)?;
+            window = window.saturating_mul(4);
+            // the window is not widened past the mate scores, which are exact there
+            if score <= alpha && alpha > Score::MIN {
+                alpha = max(score.saturating_add(-window), Score::MIN);
+            } else if score >= beta && beta < Score::MAX {
+                beta = min(score.saturating_add(window), Score::MAX);
+            } else {
+                break;
+            }
+        }
+        let node = node.as_branch();
+        assert!(node.depth == depth && node.lower == node.upper);
+        Some(())
+    }
+
+    // one search deeper with the driver of this agent
+    pub fn search<'a>(&'a self, node: &mut Node<'a>) -> Option<()> {
+        match self.driver {
+            Driver::Mtd => self.bns(node),
+            Driver::Pvs => self.aspiration(node),
+        }
+    }
+
+    // the index in `game.forward()` of the move to play after a search, which is
+    // the best child unless the strength asks for something else
+    pub fn pick<'a>(&'a self, game: Game, node: &mut Node<'a>, rng: &mut Generator) -> usize {
+        let count = game.count_moves();
+        if rng.below(100) < self.strength.blunder as usize {
+            return rng.below(count);
+        }
+        let index = |node: &Node| (0..count).find(|&i| node.is_child(i as u8)).unwrap();
+        let margin = self.strength.margin;
+        let (best, depth) = (node.get_lower(), node.get_depth());
+        if margin == 0 || best.is_mate() {
+            return index(&node.get_nodes()[0]);
+        }
+        // the children that are not much worse, weighted by how close they are
+        let threshold = best.saturating_add(-margin);
+        self.extensions.set(0);
+        self.path.borrow_mut().truncate(self.played.get());
+        let mut candidates = vec![];
+        for new_node in node.get_nodes().iter_mut() {
+            let child_beta = threshold.forward().saturating_add(1);
+            let eval = self
+                .visit(new_node, |new_node| {
+                    self.alpha_beta(new_node, child_beta, depth - 1)
+                })
+                .map(Score::backward);
+            match eval {
+                Some(eval) if eval >= threshold => {
+                    let weight = (min(eval, best).0 - threshold.0) as usize + 1;
+                    candidates.push((index(new_node), weight));
+                }
+                _ => {}
+            }
+        }
+        let total = candidates.iter().map(|&(_, weight)| weight).sum();
+        if total == 0 {
+            return index(&node.get_nodes()[0]);
+        }
+        let mut choice = rng.below(total);
+        for (i, weight) in candidates {
+            if choice < weight {
+                return i;
+            }
+            choice -= weight;
+        }
+        unreachable!()
+    }
 
-        // node.lower = -127;
-        // node.upper = 127;
-        // node.depth += 1;
-        // loop {
-        //     let mut beta = max(guess, node.lower + 1);
-        //     assert!(beta <= node.upper);
-        //     assert!(beta > node.lower);
-        //     // println!("* {}, {}, {}", node.lower, beta, node.upper);
-
-        //     let mut count = false;
-        //     let (first, rest) = node.nodes.split_first_mut().unwrap();
-        //     guess = -self.alpha_beta(first, -beta + 1, node.depth - 1);
-        //     if guess >= beta {
-        //         node.lower = guess;
-        //         if node.lower == node.upper {
-        //             return node.lower;
-        //         }
-        //         beta = node.lower + 1;
-        //         count = true;
-        //     }
-        //     for new_node in rest {
-        //         let eval = -self.alpha_beta(new_node, -beta + 1, node.depth - 1);
-        //         guess = max(guess, eval);
-        //         if eval >= beta {
-        //             swap(first, new_node);
-        //             node.lower = eval;
-        //             if node.lower == node.upper {
-        //                 return node.lower;
-        //             }
-        //             if count {
-        //                 continue;
-        //             }
-        //             beta = node.lower + 1;
-        //             count = true;
-        //         }
-        //     }
-        //     if count {
-        //         return node.lower;
-        //     }
-        //     node.upper = guess;
-        //     if node.lower == node.upper {
-        //         return node.lower;
-        //     }
-        // }
-    }
-
-    pub fn alpha_beta<'a>(&'a self, node: &mut Node<'a>, beta: i8, depth: u8) -> Option<i8> {
+    pub fn alpha_beta<'a>(&'a self, node: &mut Node<'a>, beta: Score, depth: u8) -> Option<Score> {
         if depth == 0 {
             return self.quiescence(node, beta);
         }
         if node.is_table() {
             return Some(node.get_lower());
         }
-        self.expand(node)?;
+        let extend = self.extends(node);
+        self.expand_at(node, depth)?;
         let node = node.as_branch();
         if node.depth == depth {
             if node.lower >= beta {
@@ -218,37 +782,236 @@ impl Agent {
                 return Some(node.upper);
             }
         } else {
-            node.lower = -127;
-            node.upper = 127;
+            node.lower = Score::MIN;
+            node.upper = Score::MAX;
+            node.depth = depth;
+        }
+        if extend {
+            self.extensions.set(self.extensions.get() + 1);
+            let result = self.search_children(node, beta, depth, depth);
+            self.extensions.set(self.extensions.get() - 1);
+            result
+        } else {
+            self.search_children(node, beta, depth, depth - 1)
+        }
+    }
+
+    // fail soft search in the window (alpha, beta), the first child is searched
+    // with the full window and the others with a null window around alpha
+    pub fn pvs<'a>(
+        &'a self,
+        node: &mut Node<'a>,
+        alpha: Score,
+        beta: Score,
+        depth: u8,
+    ) -> Option<Score> {
+        if depth == 0 {
+            return self.quiescence(node, beta);
+        }
+        if node.is_table() {
+            return Some(node.get_lower());
+        }
+        let extend = self.extends(node);
+        self.expand_at(node, depth)?;
+        let node = node.as_branch();
+        if node.depth == depth {
+            if node.lower >= beta || node.lower == node.upper {
+                return Some(node.lower);
+            }
+            if node.upper <= alpha {
+                return Some(node.upper);
+            }
+        } else {
+            node.lower = Score::MIN;
+            node.upper = Score::MAX;
             node.depth = depth;
         }
+        if extend {
+            self.extensions.set(self.extensions.get() + 1);
+            let result = self.pvs_children(node, alpha, beta, depth, depth);
+            self.extensions.set(self.extensions.get() - 1);
+            result
+        } else {
+            self.pvs_children(node, alpha, beta, depth, depth - 1)
+        }
+    }
+
+    fn pvs_children<'a>(
+        &'a self,
+        node: &mut Branch<'a>,
+        mut alpha: Score,
+        beta: Score,
+        depth: u8,
+        child_depth: u8,
+    ) -> Option<Score> {
+        let start = alpha;
+        let (first, rest) = node.nodes.split_first_mut().unwrap();
+        let mut best = self
+            .visit(first, |first| {
+                self.pvs(first, beta.forward(), alpha.forward(), child_depth)
+            })?
+            .backward();
+        if best >= beta {
+            self.cutoff(first, depth);
+            node.lower = within(best, node.lower, node.upper);
+            return Some(node.lower);
+        }
+        alpha = max(alpha, best);
+        let threatened = node.key & THREAT != 0;
+        for (i, new_node) in rest.iter_mut().enumerate() {
+            let test = alpha.saturating_add(1);
+            let eval = self.visit(new_node, |new_node| {
+                let eval = self.search_late(threatened, i + 1, new_node, test, child_depth)?;
+                if eval > alpha && eval < beta {
+                    Some(
+                        self.pvs(new_node, beta.forward(), alpha.forward(), child_depth)?
+                            .backward(),
+                    )
+                } else {
+                    Some(eval)
+                }
+            })?;
+            if eval > best {
+                best = eval;
+                swap(first, new_node);
+            }
+            if best >= beta {
+                self.cutoff(first, depth);
+                node.lower = within(best, node.lower, node.upper);
+                return Some(node.lower);
+            }
+            alpha = max(alpha, best);
+        }
+        let best = within(best, node.lower, node.upper);
+        if best > start {
+            node.lower = best;
+        }
+        node.upper = best;
+        debug_assert!(node.lower <= node.upper);
+        Some(best)
+    }
+
+    fn search_children<'a>(
+        &'a self,
+        node: &mut Branch<'a>,
+        beta: Score,
+        depth: u8,
+        child_depth: u8,
+    ) -> Option<Score> {
         let (first, rest) = node.nodes.split_first_mut().unwrap();
-        let mut guess = -self.alpha_beta(first, -beta + 1, depth - 1)?;
+        let child_beta = beta.forward().saturating_add(1);
+        let mut guess = self
+            .visit(first, |first| {
+                self.alpha_beta(first, child_beta, child_depth)
+            })?
+            .backward();
         if guess >= beta {
-            node.lower = guess;
-            debug_assert!(node.lower <= node.upper);
-            return Some(guess);
+            self.cutoff(first, depth);
+            node.lower = within(guess, node.lower, node.upper);
+            return Some(node.lower);
         }
-        for new_node in rest {
-            let eval = -self.alpha_beta(new_node, -beta + 1, depth - 1)?;
+        let threatened = node.key & THREAT != 0;
+        for (i, new_node) in rest.iter_mut().enumerate() {
+            let eval = self.visit(new_node, |new_node| {
+                self.search_late(threatened, i + 1, new_node, beta, child_depth)
+            })?;
             guess = max(guess, eval);
             if eval >= beta {
+                self.cutoff(new_node, depth);
                 swap(first, new_node);
-                node.lower = eval;
-                debug_assert!(node.lower <= node.upper);
+                node.lower = within(eval, node.lower, node.upper);
+                return Some(node.lower);
+            }
+        }
+        node.upper = within(guess, node.lower, node.upper);
+        Some(node.upper)
+    }
+
+    // searches a child with its position on the path, children that are drawn
+    // are a tie for either player so `search` can return the value for either
+    fn visit<'a>(
+        &'a self,
+        node: &mut Node<'a>,
+        search: impl FnOnce(&mut Node<'a>) -> Option<Score>,
+    ) -> Option<Score> {
+        if !self.draw.is_active() {
+            return search(node);
+        }
+        let mut path = self.path.borrow_mut();
+        let parent = match path.last() {
+            Some(&parent) => parent,
+            None => {
+                drop(path);
+                return search(node);
+            }
+        };
+        path.push(match node {
+            Node::Leaf(leaf) => leaf.game,
+            Node::Branch(branch) => parent.forward().nth(branch.child as usize).unwrap(),
+        });
+        let drawn = self.draw.is_draw(&path);
+        drop(path);
+        let result = if drawn {
+            Some(Score::new_tie())
+        } else {
+            search(node)
+        };
+        self.path.borrow_mut().pop();
+        result
+    }
+
+    // searches a child after the first with a null window, `test` is from the
+    // point of view of the parent and quiet children are first searched shallower
+    fn search_late<'a>(
+        &'a self,
+        threatened: bool,
+        index: usize,
+        node: &mut Node<'a>,
+        test: Score,
+        child_depth: u8,
+    ) -> Option<Score> {
+        let child_beta = test.forward().saturating_add(1);
+        let reduction = self.config.lmr_reduction;
+        if reduction > 0
+            && !threatened
+            && index >= self.config.lmr_after
+            && child_depth > reduction
+            && node.is_quiet()
+        {
+            let eval = self
+                .alpha_beta(node, child_beta, child_depth - reduction)?
+                .backward();
+            if eval < test {
                 return Some(eval);
             }
         }
-        node.upper = guess;
-        debug_assert!(node.lower <= node.upper);
-        Some(guess)
+        Some(self.alpha_beta(node, child_beta, child_depth)?.backward())
+    }
+
+    // only evasions are searched for threatened nodes, so they are cheap to extend
+    fn extends(&self, node: &Node) -> bool {
+        let key = node.raw_key();
+        (self.config.king_extension && key & THREAT != 0
+            || self.config.temple_extension && key & TEMPLE_FLAG != 0)
+            && self.extensions.get() < MAX_EXTENSIONS
     }
 
-    pub fn quiescence<'a>(&'a self, node: &mut Node<'a>, beta: i8) -> Option<i8> {
+    pub fn quiescence<'a>(&'a self, node: &mut Node<'a>, beta: Score) -> Option<Score> {
         match node {
+            // searched deeper before, which happens with reductions, it is searched
+            // again at that depth when the bounds do not decide it
+            Node::Branch(branch) if branch.depth > 0 => {
+                if branch.lower >= beta {
+                    return Some(branch.lower);
+                }
+                if branch.upper < beta {
+                    return Some(branch.upper);
+                }
+                let depth = branch.depth;
+                return self.alpha_beta(node, beta, depth);
+            }
             Node::Branch(branch) => {
-                debug_assert_eq!(branch.depth, 0);
-                debug_assert_eq!(branch.lower, branch.upper);
+                debug_assert!(branch.lower == branch.upper);
                 return Some(branch.lower);
             }
             Node::Leaf(leaf) => {
@@ -257,17 +1020,20 @@ impl Agent {
                 }
             }
         }
+        // only captures are followed, they never repeat a position so the path
+        // is not needed
         let pieces = node.piece_count();
         self.expand(node)?;
         let node = node.as_branch();
+        let child_beta = beta.forward().saturating_add(1);
         let (first, rest) = node.nodes.split_first_mut().unwrap();
         if first.piece_count() < pieces {
-            let eval = -self.quiescence(first, -beta + 1)?;
+            let eval = self.quiescence(first, child_beta)?.backward();
             node.lower = max(node.lower, eval);
         }
         for new_node in rest {
             if new_node.piece_count() < pieces {
-                let eval = -self.quiescence(new_node, -beta + 1)?;
+                let eval = self.quiescence(new_node, child_beta)?.backward();
                 node.lower = max(node.lower, eval);
                 swap(first, new_node);
             }
@@ -275,6 +1041,65 @@ impl Agent {
         node.upper = node.lower;
         Some(node.lower)
     }
+
+    // higher is searched first, the children are all leaves
+    fn order(&self, game: Game, node: &Node, depth: u8) -> u32 {
+        let leaf = match node {
+            Node::Leaf(leaf) => leaf,
+            Node::Branch(_) => unreachable!(),
+        };
+        if leaf.table && leaf.value.is_loss() {
+            return u32::MAX;
+        }
+        let key = node.key();
+        let mut order = min(self.history[key as usize].get(), HISTORY_MAX);
+        if leaf.key & TAKE != 0 {
+            order += CAPTURE;
+        }
+        if self.killers[depth as usize].get().contains(&key) {
+            order += KILLER;
+        }
+        let (from, to) = from_to(game, leaf.game);
+        if from == game.king::<My>() && temple_distance(to) < temple_distance(from) {
+            order += KING;
+        }
+        order
+    }
+
+    fn cutoff(&self, node: &Node, depth: u8) {
+        let key = node.key();
+        let history = &self.history[key as usize];
+        history.set(history.get().saturating_add(depth as u32 * depth as u32));
+        let killers = &self.killers[depth as usize];
+        let [first, _] = killers.get();
+        if first != key {
+            killers.set([key, first]);
+        }
+    }
+}
+
+// reductions and extensions make searches of the same node disagree, results
+// are kept within the bounds found before so that the drivers terminate
+fn within(value: Score, lower: Score, upper: Score) -> Score {
+    max(lower, min(upper, value))
+}
+
+// the squares of the move, seen from the player that made it
+#[inline]
+fn from_to(game: Game, new_game: Game) -> (u32, u32) {
+    let from = (game.my & !new_game.other & PIECE_MASK).trailing_zeros();
+    let to = (new_game.other & !game.my & PIECE_MASK).trailing_zeros();
+    (from, to)
+}
+
+// (card, from, to) of the move, `to` is stored as its index in the pattern of
+// the card so the key fits in `MOVE_MASK`
+#[inline]
+fn move_key(game: Game, new_game: Game) -> u16 {
+    let (from, to) = from_to(game, new_game);
+    let card = new_game.table;
+    let index = (SHIFTED[card as usize][from as usize] & ((1 << to) - 1)).count_ones();
+    ((card * 25 + from) * 4 + index) as u16
 }
 
 #[cfg(test)]
@@ -282,9 +1107,9 @@ mod test {
     use std::mem::size_of;
 
     use bumpalo::Bump;
-    use onitama_move_gen::{eval::Eval, gen::Game, tablebase::TableBase};
+    use onitama_move_gen::{eval::Eval, gen::Game, random::Generator, tablebase::TableBase};
 
-    use crate::node::{Branch, Leaf, Node};
+    use crate::node::{Agent, Branch, DrawRule, Driver, Leaf, Node, SearchConfig, Strength};
 
     #[test]
     fn try_forward_tie() {
@@ -300,4 +1125,101 @@ mod test {
         //     Test { nodes: Some(nodes) }
         // });
     }
+
+    #[test]
+    fn search_config() {
+        for config in [SearchConfig::NONE, SearchConfig::default()].iter() {
+            assert_eq!(config.to_string().parse(), Ok(*config));
+        }
+        assert_eq!("lmr=3/1,king,temple".parse(), Ok(SearchConfig::default()));
+        assert!("lmr=3".parse::<SearchConfig>().is_err());
+    }
+
+    #[test]
+    fn draw_rule() {
+        let start = Game::initial([0, 1, 2, 3, 4]);
+        let next = start.forward().next().unwrap();
+        let threefold = DrawRule::default();
+        assert!(!threefold.is_draw(&[start, next, start]));
+        assert!(threefold.is_draw(&[start, next, start, next, start]));
+        let twofold: DrawRule = "repeat=2".parse().unwrap();
+        assert!(twofold.is_draw(&[start, next, start]));
+        // the same board with the other player to move is not a repetition
+        assert!(!twofold.is_draw(&[start, start]));
+        let plies: DrawRule = "none,plies=2".parse().unwrap();
+        assert!(!plies.is_draw(&[start, next]));
+        assert!(plies.is_draw(&[start, next, start]));
+        for rule in [DrawRule::NONE, threefold, plies].iter() {
+            assert_eq!(rule.to_string().parse(), Ok(*rule));
+        }
+        assert!("repeat=x".parse::<DrawRule>().is_err());
+    }
+
+    #[test]
+    fn strength() {
+        for level in 1..=5 {
+            let strength = Strength::level(level).unwrap();
+            assert_eq!(level.to_string().parse(), Ok(strength));
+            assert_eq!(strength.to_string().parse(), Ok(strength));
+        }
+        assert_eq!("5".parse(), Ok(Strength::FULL));
+        assert_eq!(
+            "depth=2,blunder=10".parse(),
+            Ok(Strength {
+                max_depth: 2,
+                blunder: 10,
+                ..Strength::FULL
+            })
+        );
+        assert!("6".parse::<Strength>().is_err());
+        assert!("depth".parse::<Strength>().is_err());
+    }
+
+    // a deeper branch whose bounds do not decide beta is searched again
+    #[test]
+    fn quiescence_bounds() {
+        let game = Game::initial([0, 1, 2, 3, 4]);
+        let agent: Agent = Agent::new(TableBase::empty().into()).with_config(SearchConfig::NONE);
+        let mut exact = agent.new_node(game, 0);
+        while agent.bns(&mut exact).is_some() && exact.get_depth() < 2 {}
+        let value = exact.get_lower();
+
+        let mut node = agent.new_node(game, 0);
+        assert!(
+            agent
+                .alpha_beta(&mut node, value.saturating_add(1), 2)
+                .unwrap()
+                <= value
+        );
+        let branch = node.as_branch();
+        assert!(branch.lower < value && value <= branch.upper);
+        assert!(agent.quiescence(&mut node, value).unwrap() >= value);
+    }
+
+    // the aspiration window is widened up to the mate scores and not past them
+    #[test]
+    fn aspiration_mate() {
+        let mut generator = Generator::new(6);
+        for _ in 0..50 {
+            let game = generator.endgame(2);
+            if game.is_loss() || game.is_win() {
+                continue;
+            }
+            let scores: Vec<_> = [Driver::Mtd, Driver::Pvs]
+                .iter()
+                .map(|&driver| {
+                    let agent: Agent = Agent::new(TableBase::empty().into())
+                        .with_driver(driver)
+                        .with_config(SearchConfig::NONE);
+                    let mut node = agent.new_node(game, 0);
+                    while agent.search(&mut node).is_some()
+                        && node.get_depth() < 6
+                        && !node.get_lower().is_mate()
+                    {}
+                    (node.get_depth(), node.get_lower())
+                })
+                .collect();
+            assert_eq!(scores[0], scores[1], "{}", game);
+        }
+    }
 }
@@ -0,0 +1,316 @@
+use std::{
+    io::{self, BufRead, Write},
+    rc::Rc,
+};
+
+use onitama_move_gen::{
+    card::{patterns, Card, Color},
+    gen::{Game, My, Other},
+    ops::CardIter,
+    random::Generator,
+    tablebase::TableBase,
+    NAMES,
+};
+
+use crate::{
+    engine::{Engine, Limits, Player},
+    messages::translate_pos,
+    node::Strength,
+};
+
+// the squares are named like litama, blue starts on row 1
+struct Board {
+    // the game after `plies` moves
+    game: Game,
+    plies: usize,
+    human: Color,
+    // the color that made the first move
+    first: Color,
+    // the human is at the bottom unless the board is flipped
+    flipped: bool,
+}
+
+impl Board {
+    fn to_move(&self) -> Color {
+        if self.plies % 2 == 1 {
+            self.first.other()
+        } else {
+            self.first
+        }
+    }
+
+    fn human_to_move(&self) -> bool {
+        self.to_move() == self.human
+    }
+
+    // whether the square names of the player to move are flipped
+    fn flip(&self) -> bool {
+        self.to_move() == Color::Red
+    }
+
+    // blue's row 1 is at the bottom
+    fn blue_at_bottom(&self) -> bool {
+        (self.human == Color::Blue) != self.flipped
+    }
+
+    // the squares in the order they are drawn, as positions of blue
+    fn squares(&self) -> Vec<Vec<u32>> {
+        let mut rows: Vec<Vec<u32>> = (0..5)
+            .map(|row| (0..5).map(|col| row * 5 + col).collect())
+            .collect();
+        if self.blue_at_bottom() {
+            rows.reverse();
+            rows.iter_mut().for_each(|row| row.reverse());
+        }
+        rows
+    }
+
+    // `Game`'s debug layout with the kings in capitals, x for the human and o
+    // for the engine
+    fn square(&self, square: u32) -> char {
+        let game = self.game;
+        let pos = if self.flip() { 24 - square } else { square };
+        let (piece, king) = if game.my & 1 << pos != 0 {
+            ('x', game.king::<My>() == pos)
+        } else if game.other & 1 << 24 >> pos != 0 {
+            ('o', game.king::<Other>() == 24 - pos)
+        } else {
+            return '.';
+        };
+        let piece = match (piece, self.human_to_move()) {
+            ('x', false) => 'o',
+            ('o', false) => 'x',
+            _ => piece,
+        };
+        if king {
+            piece.to_ascii_uppercase()
+        } else {
+            piece
+        }
+    }
+
+    // the cards of blue or red, drawn like the board
+    fn cards(&self, cards: &[(u32, bool)]) -> String {
+        let cards: Vec<_> = cards
+            .iter()
+            .map(|&(id, blue)| (Card::new(id).unwrap(), blue != self.blue_at_bottom()))
+            .collect();
+        patterns(&cards)
+    }
+
+    fn render(&self) -> String {
+        let game = self.game;
+        let mover_blue = !self.flip();
+        let mover = CardIter::new(game.cards & 0xffff).map(|card| (card, mover_blue));
+        let other = CardIter::new(game.cards >> 16).map(|card| (card, !mover_blue));
+        let (human, engine): (Vec<_>, Vec<_>) = if self.human_to_move() {
+            (mover.collect(), other.collect())
+        } else {
+            (other.collect(), mover.collect())
+        };
+
+        let mut text = format!("engine:\n{}\n", self.cards(&engine));
+        let squares = self.squares();
+        for row in &squares {
+            let rank = &translate_pos(row[0] as usize, false)[1..];
+            let row: String = row.iter().map(|&square| self.square(square)).collect();
+            text += &format!("  {} {}\n", rank, row);
+        }
+        let files: String = squares[0]
+            .iter()
+            .map(|&square| translate_pos(square as usize, false).remove(0))
+            .collect();
+        text += &format!("    {}\n\n", files);
+        text += &format!("you:\n{}\n", self.cards(&human));
+        text += &format!("side:\n{}", self.cards(&[(game.table, mover_blue)]));
+        text
+    }
+
+    // like "tiger c1c3", from and to as litama squares
+    fn move_name(&self, new_game: Game) -> String {
+        let from = self.game.my & !new_game.other;
+        let to = new_game.other & !self.game.my;
+        format!(
+            "{} {}{}",
+            NAMES[new_game.table as usize],
+            translate_pos(from.trailing_zeros() as usize, self.flip()),
+            translate_pos(to.trailing_zeros() as usize, self.flip())
+        )
+    }
+
+    fn parse_move(&self, text: &str) -> Option<Game> {
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        self.game
+            .forward()
+            .find(|&new_game| self.move_name(new_game) == text)
+    }
+}
+
+const HELP: &str = "commands:
+  <card> <from><to>  play a move, like \"tiger c1c3\"
+  undo               take back your last move
+  hint               ask the engine for a move
+  flip               turn the board around
+  quit";
+
+// usage: onitama play [blue|red] [ms per move]
+// plays a random deal against the engine in the terminal, the color of the
+// side card moves first
+pub fn run(args: &[String], player: Player) {
+    let human = match args.first().map(String::as_str) {
+        None | Some("blue") => Color::Blue,
+        Some("red") => Color::Red,
+        Some(_) => {
+            eprintln!("usage: onitama play [blue|red] [ms per move]");
+            return;
+        }
+    };
+    let millis: u64 = match args.get(1) {
+        Some(millis) => millis.parse().expect("invalid time per move"),
+        None => 1000,
+    };
+
+    let mut rng = Generator::default();
+    let cards = rng.deal();
+    println!("building the tablebase");
+    let tablebase: Rc<TableBase> = TableBase::new(cards).into();
+    let mut engine = player.engine(rng.seed());
+    engine.new_game(tablebase.clone());
+    // hints are always at full strength
+    let hint_player = Player {
+        strength: Strength::FULL,
+        ..player
+    };
+    let mut hints = hint_player.engine(rng.seed());
+    hints.new_game(tablebase);
+    let limits = Limits {
+        millis,
+        ..Limits::default()
+    };
+
+    let mut history = vec![Game::initial(cards)];
+    let mut board = Board {
+        game: history[0],
+        plies: 0,
+        human,
+        first: Card::new(cards[4]).unwrap().color(),
+        flipped: false,
+    };
+    println!("{}", HELP);
+    println!("you play {}, {} moves first", human, board.first);
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        board.game = *history.last().unwrap();
+        board.plies = history.len() - 1;
+        println!("\n{}", board.render());
+
+        let ended = if board.game.is_loss() {
+            let winner = if board.human_to_move() {
+                "the engine"
+            } else {
+                "you"
+            };
+            Some(format!("{} won", winner))
+        } else if player.draw.is_draw(&history) {
+            Some("draw".to_string())
+        } else {
+            None
+        };
+        if ended.is_none() && !board.human_to_move() {
+            engine.set_position(&history);
+            let result = engine.go(limits, &mut |_| {});
+            let new_game = board.game.forward().nth(result.index).unwrap();
+            println!("engine plays {}", board.move_name(new_game));
+            history.push(new_game);
+            continue;
+        }
+        match &ended {
+            Some(ended) => print!("{}, undo or quit> ", ended),
+            None => print!("> "),
+        }
+        io::stdout().flush().unwrap();
+
+        let line = match lines.next() {
+            Some(line) => line.expect("could not read stdin"),
+            None => return,
+        };
+        match line.trim() {
+            "" => {}
+            "quit" => return,
+            "help" => println!("{}", HELP),
+            "flip" => board.flipped = !board.flipped,
+            "undo" => {
+                // back to the last position where it was your move
+                while history.len() > 1 {
+                    history.pop();
+                    board.plies = history.len() - 1;
+                    if board.human_to_move() {
+                        break;
+                    }
+                }
+            }
+            "hint" if ended.is_none() => {
+                hints.set_position(&history);
+                let result = hints.go(limits, &mut |_| {});
+                let new_game = board.game.forward().nth(result.index).unwrap();
+                println!("hint: {}", board.move_name(new_game));
+            }
+            text if ended.is_none() => match board.parse_move(text) {
+                Some(new_game) => history.push(new_game),
+                None => println!("not a legal move: {}, try help", text),
+            },
+            _ => println!("the game is over"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn play_board() {
+        // crab has a blue stamp
+        let game = Game::initial([0, 1, 2, 3, 4]);
+        let mut board = Board {
+            game,
+            plies: 0,
+            human: Color::Blue,
+            first: Color::Blue,
+            flipped: false,
+        };
+        let text = board.render();
+        assert!(text.contains("  5 ooOoo\n"));
+        assert!(text.contains("  1 xxXxx\n    abcde\n"));
+        // ox moves forward, right and back
+        assert!(text.contains("ox        boar\n.....     .....\n..#..     ..#..\n"));
+
+        let new_game = board.parse_move("ox  c1c2").unwrap();
+        assert_eq!(board.move_name(new_game), "ox c1c2");
+        assert_eq!(board.parse_move("ox c1c3"), None);
+
+        // the engine moves with row 5 as its first row
+        board.game = new_game;
+        board.plies = 1;
+        board.flipped = true;
+        let text = board.render();
+        assert!(text.contains("  1 xx.xx\n"));
+        assert!(text.contains("  2 ..X..\n"));
+        assert!(board.parse_move("horse c5c4").is_some());
+
+        // red moves first with the blue pieces at the top when it plays red
+        let game = Game::initial([0, 1, 2, 3, 10]);
+        assert_eq!(Card::new(10).unwrap().color(), Color::Red);
+        let board = Board {
+            game,
+            plies: 0,
+            human: Color::Red,
+            first: Color::Red,
+            flipped: false,
+        };
+        assert!(board.human_to_move());
+        assert!(board.render().contains("  1 ooOoo\n"));
+        assert!(board.parse_move("horse c5c4").is_some());
+    }
+}
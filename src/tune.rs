@@ -0,0 +1,153 @@
+use std::{cmp::Ordering, fs, rc::Rc, time::Instant};
+
+use onitama_move_gen::{
+    eval::Eval,
+    gen::Game,
+    heuristic::Weights,
+    random::Generator,
+    tablebase::TableBase,
+    tune::{fit_scale, sigmoid, tune, Sample},
+};
+
+use crate::node::{Agent, Evaluator};
+
+// games that take longer than this are counted as a draw
+const MAX_PLIES: usize = 200;
+// maps search scores to expected scores when they are used as labels
+const SEARCH_SCALE: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Label {
+    // the result of the self-play game
+    Outcome,
+    // the score of the search that picked the move
+    Search,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    pub depth: u8,
+    // the first moves of every game are random to get different games
+    pub random_plies: usize,
+    pub label: Label,
+}
+
+// plays one game with the heuristic evaluation and labels every position
+// that was searched, positions in the tablebase end the game
+pub fn self_play(
+    tablebase: &Rc<TableBase>,
+    weights: Weights,
+    mut game: Game,
+    config: Config,
+    rng: &mut Generator,
+) -> Vec<Sample> {
+    let mut positions = vec![];
+    // the result for the player to move in the last position
+    let result = loop {
+        if game.is_loss() {
+            break 0.0;
+        }
+        if let Some(eval) = tablebase.probe(game) {
+            break match eval.cmp(&Eval::new_tie()) {
+                Ordering::Greater => 1.0,
+                Ordering::Equal => 0.5,
+                Ordering::Less => 0.0,
+            };
+        }
+        if positions.len() == MAX_PLIES {
+            break 0.5;
+        }
+
+        let agent: Agent =
+            Agent::new(tablebase.clone()).with_evaluator(Evaluator::Heuristic(weights));
+        let mut node = agent.new_node(game, 0);
+        for _ in 0..config.depth {
+            if agent.search(&mut node).is_none() {
+                break;
+            }
+        }
+        positions.push((game, node.get_lower()));
+
+        let i = if positions.len() <= config.random_plies {
+            rng.below(game.count_moves())
+        } else {
+            let first = &node.get_nodes()[0];
+            (0..game.count_moves())
+                .find(|&i| first.is_child(i as u8))
+                .unwrap()
+        };
+        game = game.forward().nth(i).unwrap();
+    };
+
+    let plies = positions.len();
+    positions
+        .into_iter()
+        .enumerate()
+        .map(|(ply, (game, score))| {
+            let target = match config.label {
+                Label::Outcome if (plies - ply) % 2 == 0 => result,
+                Label::Outcome => 1.0 - result,
+                Label::Search if score.is_win() => 1.0,
+                Label::Search if score.is_loss() => 0.0,
+                Label::Search => sigmoid(SEARCH_SCALE, score.0 as f64),
+            };
+            Sample::new(game, target)
+        })
+        .collect()
+}
+
+// usage: onitama tune <output file> <deals> <games per deal> <depth> [outcome|search]
+pub fn run(args: &[String], initial: Weights) {
+    if args.len() < 4 {
+        eprintln!(
+            "usage: onitama tune <output file> <deals> <games per deal> <depth> [outcome|search]"
+        );
+        return;
+    }
+    let deals: usize = args[1].parse().expect("invalid number of deals");
+    let games: usize = args[2].parse().expect("invalid number of games");
+    let config = Config {
+        depth: args[3].parse().expect("invalid depth"),
+        random_plies: 4,
+        label: match args.get(4).map(String::as_str) {
+            None | Some("outcome") => Label::Outcome,
+            Some("search") => Label::Search,
+            Some(label) => panic!("unknown label: {}", label),
+        },
+    };
+
+    let now = Instant::now();
+    let mut rng = Generator::default();
+    let mut samples = vec![];
+    for deal in 0..deals {
+        let cards = rng.deal();
+        let tablebase: Rc<TableBase> = TableBase::new(cards).into();
+        for _ in 0..games {
+            samples.extend(self_play(
+                &tablebase,
+                initial,
+                Game::initial(cards),
+                config,
+                &mut rng,
+            ));
+        }
+        println!(
+            "deal {}: {} samples, {}s",
+            deal,
+            samples.len(),
+            now.elapsed().as_secs()
+        );
+    }
+
+    let scale = match config.label {
+        Label::Outcome => fit_scale(&samples, initial),
+        Label::Search => SEARCH_SCALE,
+    };
+    println!("scale: {}", scale);
+    let weights = tune(&samples, initial, scale, |iteration, weights, loss| {
+        println!("iteration {}: {:?}, loss {}", iteration, weights, loss)
+    });
+
+    fs::write(&args[0], weights.to_string()).expect("could not write the weights");
+    println!("{}", weights);
+}
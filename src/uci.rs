@@ -0,0 +1,283 @@
+use std::{
+    io::{self, BufRead},
+    rc::Rc,
+    slice::Iter,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{channel, Receiver},
+        Arc,
+    },
+    thread,
+};
+
+use onitama_move_gen::{
+    gen::Game,
+    notation::{card_id, move_name},
+    random::Generator,
+    score::Score,
+    tablebase::TableBase,
+};
+
+use crate::engine::{Engine, Limits, Player, SearchResult};
+
+// usage: onitama uci
+// a text protocol in the spirit of UCI, one command per line on stdin:
+//   uci, isready, ucinewgame, quit
+//   setoption name tablebase value <true|false>
+//   position <notation>|startpos <5 cards> [moves <card> <from><to> ...]
+//   go [movetime <ms>] [depth <plies>] [infinite] [ponder]
+//   stop, ponderhit
+// stop, ponderhit and isready are handled right away while `go` runs
+// startpos takes blue's two cards, red's two cards and the side card like
+// `Game::initial`
+// a search writes "info depth .. score .. nodes .. time .. pv .." lines and
+// ends with "bestmove <card> <from><to>", moves are written like `move_name`
+pub fn run(player: Player) {
+    let (lines, receiver) = channel();
+    let (signals_sender, signals) = channel();
+    // the number of `go` commands that were read and did not write a bestmove yet
+    let searches = Arc::new(AtomicUsize::new(0));
+    let done = searches.clone();
+    // the engine is not `Send`, so it lives on the thread that searches and
+    // this thread only reads commands and handles the ones that can not wait
+    // for `go` to return
+    let worker = thread::spawn(move || {
+        let engine = player.engine(Generator::default().seed());
+        signals_sender.send(engine.signals().clone()).unwrap();
+        Session::new(engine, done).run(receiver);
+    });
+    let signals = signals.recv().unwrap();
+
+    for line in io::stdin().lock().lines() {
+        let line = line.expect("could not read stdin");
+        // without a search they are handled in order with the other commands
+        let searching = searches.load(Ordering::SeqCst) > 0;
+        match line.trim() {
+            "stop" if searching => signals.stop(),
+            "ponderhit" if searching => signals.ponderhit(),
+            "isready" if searching => println!("readyok"),
+            "quit" => break,
+            trimmed => {
+                if trimmed.split_whitespace().next() == Some("go") {
+                    signals.reset();
+                    searches.fetch_add(1, Ordering::SeqCst);
+                }
+                lines.send(line).unwrap();
+            }
+        }
+    }
+    signals.stop();
+    drop(lines);
+    worker.join().unwrap();
+}
+
+struct Session {
+    engine: Box<dyn Engine>,
+    // the cards of the tablebase of the engine, as a mask
+    deal: Option<u32>,
+    // building the tablebase takes a while, without it endgames are not played
+    // perfectly
+    tablebase: bool,
+    history: Vec<Game>,
+    // counted down when a `go` is done
+    searches: Arc<AtomicUsize>,
+}
+
+impl Session {
+    fn new(engine: Box<dyn Engine>, searches: Arc<AtomicUsize>) -> Self {
+        Self {
+            engine,
+            deal: None,
+            tablebase: true,
+            history: vec![],
+            searches,
+        }
+    }
+
+    fn run(&mut self, lines: Receiver<String>) {
+        for line in lines {
+            let args: Vec<&str> = line.split_whitespace().collect();
+            if let Err(err) = self.command(&args) {
+                println!("info string {}", err);
+            }
+        }
+    }
+
+    fn command(&mut self, args: &[&str]) -> Result<(), String> {
+        match args {
+            [] => {}
+            ["uci"] => {
+                println!("id name onitama");
+                println!("option name tablebase type check default true");
+                println!("uciok");
+            }
+            ["isready"] => println!("readyok"),
+            ["stop"] => self.engine.stop(),
+            ["ponderhit"] => self.engine.ponderhit(),
+            ["ucinewgame"] => self.deal = None,
+            ["setoption", "name", "tablebase", "value", value] => {
+                self.tablebase = value.parse().map_err(|_| "expected true or false")?;
+                self.deal = None;
+            }
+            ["position", position @ ..] => {
+                let history = parse_position(position)?;
+                self.new_game(history[0]);
+                self.history = history;
+            }
+            ["go", limits @ ..] => {
+                let result = parse_go(limits).and_then(|limits| self.go(limits));
+                self.searches.fetch_sub(1, Ordering::SeqCst);
+                result?
+            }
+            _ => return Err(format!("unknown command: {}", args.join(" "))),
+        }
+        Ok(())
+    }
+
+    // starts a new game for the engine when the deal changed
+    fn new_game(&mut self, game: Game) {
+        let deal = game.cards & 0xffff | game.cards >> 16 | 1 << game.table;
+        if self.deal == Some(deal) {
+            return;
+        }
+        let tablebase = if self.tablebase {
+            TableBase::new(game.all_cards())
+        } else {
+            TableBase::empty()
+        };
+        self.engine.new_game(Rc::from(tablebase));
+        self.deal = Some(deal);
+    }
+
+    fn go(&mut self, limits: Limits) -> Result<(), String> {
+        let game = *self.history.last().ok_or("no position was set")?;
+        if game.is_loss() {
+            println!("bestmove (none)");
+            return Ok(());
+        }
+        self.engine.set_position(&self.history);
+        let result = self.engine.go(limits, &mut |result| {
+            println!(
+                "info depth {} score {} nodes {} time {} pv {}",
+                result.depth,
+                result.score.map_or("none".to_string(), score),
+                result.nodes,
+                result.millis,
+                pv_names(game, result),
+            );
+        });
+        let new_game = game.forward().nth(result.index).unwrap();
+        println!("bestmove {}", move_name(game, new_game));
+        Ok(())
+    }
+}
+
+// "mate <plies>" for proven results, negative when the engine is lost, and
+// "cp <value>" for everything else
+fn score(score: Score) -> String {
+    if score.is_win() {
+        format!("mate {}", score.plies())
+    } else if score.is_loss() {
+        format!("mate -{}", score.plies())
+    } else {
+        format!("cp {}", score.0)
+    }
+}
+
+fn pv_names(mut game: Game, result: &SearchResult) -> String {
+    let mut names = vec![];
+    for &index in &result.pv {
+        let new_game = game.forward().nth(index).unwrap();
+        names.push(move_name(game, new_game));
+        game = new_game;
+    }
+    names.join(" ")
+}
+
+// every position from the given one to the last move
+fn parse_position(args: &[&str]) -> Result<Vec<Game>, String> {
+    let split = args.iter().position(|&arg| arg == "moves");
+    let (position, moves) = args.split_at(split.unwrap_or(args.len()));
+    let game = match position {
+        ["startpos", names @ ..] if names.len() == 5 => {
+            let mut cards = [0; 5];
+            for (card, name) in cards.iter_mut().zip(names) {
+                *card = card_id(name).ok_or_else(|| format!("unknown card: {}", name))?;
+            }
+            Game::initial(cards)
+        }
+        _ => position
+            .join(" ")
+            .parse()
+            .map_err(|err| format!("{}", err))?,
+    };
+
+    let mut history = vec![game];
+    // a move is a card and the squares, like "boar e1d2"
+    for name in moves.get(1..).unwrap_or(&[]).chunks(2) {
+        let name = name.join(" ");
+        let game = *history.last().unwrap();
+        if game.is_loss() {
+            return Err(format!("the game is over before {}", name));
+        }
+        let new_game = game
+            .forward()
+            .find(|&new_game| move_name(game, new_game) == name)
+            .ok_or_else(|| format!("illegal move: {}", name))?;
+        history.push(new_game);
+    }
+    Ok(history)
+}
+
+fn parse_go(args: &[&str]) -> Result<Limits, String> {
+    fn number<T: FromStr>(args: &mut Iter<&str>, name: &str) -> Result<T, String> {
+        args.next()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format!("expected a number after {}", name))
+    }
+
+    // no limits is an infinite search
+    let mut limits = Limits::default();
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        match arg {
+            "movetime" => limits.millis = number(&mut args, arg)?,
+            "depth" => limits.depth = number(&mut args, arg)?,
+            "infinite" => {}
+            "ponder" => limits.ponder = true,
+            _ => return Err(format!("unknown go option: {}", arg)),
+        }
+    }
+    Ok(limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        let history = parse_position(&["startpos", "ox", "boar", "horse", "elephant", "crab"]);
+        assert_eq!(history, Ok(vec![Game::initial([0, 1, 2, 3, 4])]));
+
+        let notation = "xxXxx/...../...../...../ooOoo ox boar horse elephant crab";
+        let mut args: Vec<&str> = notation.split(' ').collect();
+        args.extend(&["moves", "ox", "c1c2", "horse", "c1c2"]);
+        let history = parse_position(&args).unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0], Game::initial([0, 1, 2, 3, 4]));
+        assert_eq!(move_name(history[1], history[2]), "horse c1c2");
+
+        args.push("ox");
+        assert!(parse_position(&args).is_err());
+        assert!(parse_position(&["startpos", "ox"]).is_err());
+
+        let limits = parse_go(&["depth", "3", "movetime", "200"]).unwrap();
+        assert_eq!(limits.depth, 3);
+        assert_eq!(limits.millis, 200);
+        assert_eq!(parse_go(&["infinite"]), Ok(Limits::default()));
+        assert!(parse_go(&["depth"]).is_err());
+        assert!(parse_go(&["nodes", "100"]).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Command, Stdio},
+};
+
+// pipes a short session through the binary, without a tablebase to keep it fast
+#[test]
+fn uci_session() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_onitama"))
+        .arg("uci")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("could not start the engine");
+    let mut stdin = child.stdin.take().unwrap();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut lines = stdout.lines().map(Result::unwrap);
+    // every line up to and including the first one that starts with `prefix`
+    let mut until = |prefix: &str| {
+        let mut read = vec![];
+        for line in &mut lines {
+            let done = line.starts_with(prefix);
+            read.push(line);
+            if done {
+                return read;
+            }
+        }
+        panic!("the engine stopped before {}: {:?}", prefix, read);
+    };
+
+    writeln!(stdin, "uci").unwrap();
+    assert!(until("uciok").contains(&"id name onitama".to_string()));
+
+    writeln!(stdin, "setoption name tablebase value false").unwrap();
+    writeln!(stdin, "frobnicate").unwrap();
+    writeln!(stdin, "isready").unwrap();
+    let read = until("readyok");
+    assert_eq!(read[0], "info string unknown command: frobnicate");
+
+    writeln!(
+        stdin,
+        "position startpos ox boar horse elephant crab moves ox c1c2"
+    )
+    .unwrap();
+    writeln!(stdin, "go depth 2").unwrap();
+    let read = until("bestmove");
+    let info = &read[read.len() - 2];
+    assert!(info.starts_with("info depth 2 score "), "{}", info);
+    // the best move is the first move of the last line
+    let best = read.last().unwrap().strip_prefix("bestmove ").unwrap();
+    let pv = info.split(" pv ").nth(1).unwrap();
+    assert!(pv.starts_with(best), "{} {}", best, pv);
+
+    // isready is answered while the search runs, and a stop right after go is
+    // not lost
+    writeln!(stdin, "go infinite").unwrap();
+    writeln!(stdin, "isready").unwrap();
+    let read = until("readyok");
+    assert!(read.iter().all(|line| !line.starts_with("bestmove")));
+    writeln!(stdin, "stop").unwrap();
+    until("bestmove");
+    writeln!(stdin, "go infinite").unwrap();
+    writeln!(stdin, "stop").unwrap();
+    until("bestmove");
+
+    writeln!(stdin, "quit").unwrap();
+    assert!(child.wait().unwrap().success());
+}